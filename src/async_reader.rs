@@ -0,0 +1,338 @@
+//! The asynchronous counterpart of [`SeekableReader`](crate::SeekableReader).
+//!
+//! The runtime-agnostic parts live in [`AsyncSeekableReader`] itself. The trait
+//! implementations for each runtime only translate how the inner reader is polled.
+use crate::window::{self, Window};
+use crate::{CacheStore, OutOfWindowPolicy, RingStore, CHUNK_SIZE};
+use core::cmp::min;
+use core::ops::Range;
+use core::task::{ready, Context, Poll};
+use std::io::{Result, SeekFrom};
+
+/// A seek which needs data from the inner reader to complete
+struct PendingSeek {
+    /// The position passed to the seek
+    #[cfg_attr(not(feature = "futures-io"), allow(dead_code))]
+    requested: SeekFrom,
+    target: Target,
+}
+
+enum Target {
+    Forward(u64),
+    End { shift: i64, old_position: u64 },
+}
+
+/// An asynchronous reader adapter that allows to seek a little bit
+///
+/// This is the asynchronous counterpart of [`SeekableReader`](crate::SeekableReader), with the same
+/// window semantics. Seeking forwards beyond the data read so far, and seeking relative
+/// to the end of the stream, complete once the necessary data was read from `inner`.
+pub struct AsyncSeekableReader<R, S: CacheStore = RingStore> {
+    pub inner: R,
+    window: Window<S>,
+    seek: Option<PendingSeek>,
+}
+
+impl<R> AsyncSeekableReader<R> {
+    /// Create a new instance of an AsyncSeekableReader.
+    ///
+    /// It wraps around `inner` and allows seeking backwards by
+    /// keeping at least `keep_size` bytes of already read data,
+    /// if this amount of data is already read.
+    ///
+    /// At most, `2 * keep_size` bytes are kept.
+    pub fn new(inner: R, keep_size: usize) -> AsyncSeekableReader<R> {
+        AsyncSeekableReader::with_store(inner, keep_size, RingStore::with_capacity(2 * keep_size))
+    }
+}
+
+impl<R, S: CacheStore> AsyncSeekableReader<R, S> {
+    /// Create a new AsyncSeekableReader which keeps its cache in `store`.
+    pub fn with_store(inner: R, keep_size: usize, store: S) -> AsyncSeekableReader<R, S> {
+        AsyncSeekableReader {
+            inner,
+            window: Window::new(keep_size, store),
+            seek: None,
+        }
+    }
+
+    /// Sets what happens when seeking backwards to data that is no longer cached.
+    ///
+    /// By default, such seeks fail with a [`SeekOutOfWindow`](crate::SeekOutOfWindow) error.
+    pub fn set_out_of_window_policy(&mut self, policy: OutOfWindowPolicy) {
+        self.window.out_of_window = policy;
+    }
+
+    /// Returns how many bytes are kept at least for seeking backwards.
+    pub fn keep_size(&self) -> usize {
+        self.window.keep_size
+    }
+
+    /// Returns the stream position of `inner`, which is behind all data read so far.
+    pub fn read_bytes(&self) -> u64 {
+        self.window.read_bytes()
+    }
+
+    /// Returns the stream positions that are cached and can be seeked to without reading `inner`.
+    pub fn window_range(&self) -> Range<u64> {
+        self.window.range()
+    }
+
+    /// Returns a reference to the store holding the cached data.
+    pub fn store(&self) -> &S {
+        self.window.store()
+    }
+
+    /// Returns the size of the buffered data.
+    pub fn buffered_size(&self) -> usize {
+        self.store().len() as usize
+    }
+
+    /// Returns the total length of the stream, if `inner` already reached EOF or it was told.
+    pub fn stream_len(&self) -> Option<u64> {
+        self.window.stream_len()
+    }
+
+    /// Tells the length of the stream, see [`SeekableReader::set_len_hint`](crate::SeekableReader::set_len_hint).
+    pub fn set_len_hint(&mut self, len: u64) {
+        self.window.set_len_hint(len);
+    }
+
+    pub fn get_stream_position(&self) -> u64 {
+        self.window.pos()
+    }
+
+    /// Reads from `inner` into the cache, if there is no cached data at the stream position.
+    fn poll_fill<F>(&mut self, cx: &mut Context<'_>, poll_read: F) -> Poll<Result<&[u8]>>
+    where
+        F: FnOnce(&mut R, &mut Context<'_>, &mut [u8]) -> Poll<Result<usize>>,
+    {
+        if self.window.at_end() {
+            let mut chunk = [0; CHUNK_SIZE];
+            let chunk = &mut chunk[..self.window.keep_size.clamp(1, CHUNK_SIZE)];
+            let read_bytes = ready!(poll_read(&mut self.inner, cx, chunk))?;
+            self.window.append(&chunk[..read_bytes], chunk.len())?;
+        }
+        Poll::Ready(self.window.cached())
+    }
+
+    /// Reads into `buf`, from the cache if possible, and from `inner` otherwise.
+    fn poll_read_into<F>(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+        poll_read: F,
+    ) -> Poll<Result<usize>>
+    where
+        F: FnOnce(&mut R, &mut Context<'_>, &mut [u8]) -> Poll<Result<usize>>,
+    {
+        if !self.window.at_end() {
+            return Poll::Ready(self.window.read_cached(buf));
+        }
+        let read_bytes = ready!(poll_read(&mut self.inner, cx, buf))?;
+        self.window.append_consumed(&buf[..read_bytes], buf.len())?;
+        Poll::Ready(Ok(read_bytes))
+    }
+
+    /// Starts a seek, which is completed by [`AsyncSeekableReader::poll_seek`].
+    fn start_seek(&mut self, pos: SeekFrom) -> Result<()> {
+        let old_position = self.window.pos();
+        let target = match self.window.seek(pos)? {
+            window::Seek::Done(_) => None,
+            window::Seek::Forward(target) => Some(Target::Forward(target)),
+            window::Seek::End(shift) => Some(Target::End {
+                shift,
+                old_position,
+            }),
+            window::Seek::Reopen(target) => return Err(self.window.out_of_window_error(target)),
+        };
+        self.seek = target.map(|target| PendingSeek {
+            requested: pos,
+            target,
+        });
+        Ok(())
+    }
+
+    /// Reads from `inner` until the pending seek, if any, can be completed.
+    ///
+    /// The seek stays pending only on `Poll::Pending`, so a failed seek is not resumed later.
+    fn poll_seek<F>(&mut self, cx: &mut Context<'_>, poll_read: F) -> Poll<Result<u64>>
+    where
+        F: FnMut(&mut R, &mut Context<'_>, &mut [u8]) -> Poll<Result<usize>>,
+    {
+        let Some(seek) = self.seek.take() else {
+            return Poll::Ready(Ok(self.window.pos()));
+        };
+        let poll = self.poll_target(cx, &seek.target, poll_read);
+        if poll.is_pending() {
+            self.seek = Some(seek);
+        }
+        poll
+    }
+
+    /// Reads from `inner` until the seek to `target` can be completed.
+    fn poll_target<F>(
+        &mut self,
+        cx: &mut Context<'_>,
+        target: &Target,
+        mut poll_read: F,
+    ) -> Poll<Result<u64>>
+    where
+        F: FnMut(&mut R, &mut Context<'_>, &mut [u8]) -> Poll<Result<usize>>,
+    {
+        let mut chunk = [0; CHUNK_SIZE];
+        let chunk_size = self.window.keep_size.clamp(1, CHUNK_SIZE);
+        loop {
+            let at_eof = self.window.at_eof();
+            let missing = match *target {
+                Target::Forward(target) if at_eof || target <= self.window.read_bytes() => {
+                    return Poll::Ready(self.seek_to(target));
+                }
+                Target::End {
+                    shift,
+                    old_position,
+                } if at_eof => {
+                    return Poll::Ready(self.seek_from_end(shift, old_position));
+                }
+                Target::Forward(target) => target - self.window.read_bytes(),
+                Target::End { .. } => chunk_size as u64,
+            };
+            let chunk = &mut chunk[..min(missing, chunk_size as u64) as usize];
+            let read_bytes = ready!(poll_read(&mut self.inner, cx, chunk))?;
+            self.window
+                .append_consumed(&chunk[..read_bytes], chunk.len())?;
+        }
+    }
+
+    /// Completes a seek relative to the end once EOF was reached.
+    ///
+    /// `inner` cannot be recreated, so targets that are no longer cached are out of the window.
+    fn seek_from_end(&mut self, shift: i64, old_position: u64) -> Result<u64> {
+        match self.window.seek_from_end(shift, old_position)? {
+            window::Seek::Done(pos) => Ok(pos),
+            window::Seek::Reopen(target) => {
+                self.window.return_to(old_position);
+                Err(self.window.out_of_window_error(target))
+            }
+            _ => unreachable!("the stream length is known at EOF"),
+        }
+    }
+
+    /// Completes a forward seek once enough data was read, or EOF was reached.
+    fn seek_to(&mut self, target: u64) -> Result<u64> {
+        match self.window.seek(SeekFrom::Start(target))? {
+            window::Seek::Done(pos) => Ok(pos),
+            _ => unreachable!("the target was read"),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_impl {
+    use super::AsyncSeekableReader;
+    use crate::CacheStore;
+    use core::pin::Pin;
+    use core::task::{ready, Context, Poll};
+    use std::io::{Result, SeekFrom};
+    use tokio::io::{AsyncBufRead, AsyncRead, AsyncSeek, ReadBuf};
+
+    fn poll_read<R: AsyncRead + Unpin>(
+        inner: &mut R,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        let mut buf = ReadBuf::new(buf);
+        ready!(Pin::new(inner).poll_read(cx, &mut buf))?;
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+
+    impl<R: AsyncRead + Unpin, S: CacheStore + Unpin> AsyncRead for AsyncSeekableReader<R, S> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<Result<()>> {
+            let read_bytes =
+                ready!(self
+                    .get_mut()
+                    .poll_read_into(cx, buf.initialize_unfilled(), poll_read))?;
+            buf.advance(read_bytes);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl<R: AsyncRead + Unpin, S: CacheStore + Unpin> AsyncBufRead for AsyncSeekableReader<R, S> {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<&[u8]>> {
+            self.get_mut().poll_fill(cx, poll_read)
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            self.get_mut().window.consume(amt);
+        }
+    }
+
+    impl<R: AsyncRead + Unpin, S: CacheStore + Unpin> AsyncSeek for AsyncSeekableReader<R, S> {
+        fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> Result<()> {
+            self.get_mut().start_seek(position)
+        }
+
+        fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<u64>> {
+            self.get_mut().poll_seek(cx, poll_read)
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+mod futures_impl {
+    use super::AsyncSeekableReader;
+    use crate::CacheStore;
+    use core::pin::Pin;
+    use core::task::{Context, Poll};
+    use futures_io::{AsyncBufRead, AsyncRead, AsyncSeek};
+    use std::io::{Result, SeekFrom};
+
+    fn poll_read<R: AsyncRead + Unpin>(
+        inner: &mut R,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        Pin::new(inner).poll_read(cx, buf)
+    }
+
+    impl<R: AsyncRead + Unpin, S: CacheStore + Unpin> AsyncRead for AsyncSeekableReader<R, S> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<Result<usize>> {
+            self.get_mut().poll_read_into(cx, buf, poll_read)
+        }
+    }
+
+    impl<R: AsyncRead + Unpin, S: CacheStore + Unpin> AsyncBufRead for AsyncSeekableReader<R, S> {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<&[u8]>> {
+            self.get_mut().poll_fill(cx, poll_read)
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            self.get_mut().window.consume(amt);
+        }
+    }
+
+    impl<R: AsyncRead + Unpin, S: CacheStore + Unpin> AsyncSeek for AsyncSeekableReader<R, S> {
+        /// Seeks to `pos`, reading from `inner` as far as necessary.
+        ///
+        /// A seek that was interrupted by `Poll::Pending` resumes with the next call.
+        fn poll_seek(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            pos: SeekFrom,
+        ) -> Poll<Result<u64>> {
+            let this = self.get_mut();
+            if this.seek.as_ref().map(|seek| seek.requested) != Some(pos) {
+                this.start_seek(pos)?;
+            }
+            this.poll_seek(cx, poll_read)
+        }
+    }
+}
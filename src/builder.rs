@@ -0,0 +1,252 @@
+//! Configuring a [`SeekableReader`] in one place, see [`SeekableReaderBuilder`].
+use crate::io::{Error, ErrorKind, Read, Result};
+use crate::{OutOfWindowPolicy, Reopen, RingStore, SeekableReader, CHUNK_SIZE};
+use alloc::boxed::Box;
+
+/// A builder for a [`SeekableReader`], see [`SeekableReader::builder`]
+///
+/// The options are checked against each other by [`SeekableReaderBuilder::build`].
+pub struct SeekableReaderBuilder<R> {
+    keep_size: usize,
+    /// The policy if it was set, otherwise it follows from whether `reopen` is set
+    out_of_window: Option<OutOfWindowPolicy>,
+    reopen: Option<Reopen<R>>,
+    min_read: usize,
+    head_len: usize,
+    tail_len: usize,
+    stream_len: Option<u64>,
+    eager: bool,
+}
+
+impl<R: Read> SeekableReader<R> {
+    /// Returns a builder to configure a new SeekableReader.
+    ///
+    /// Without further options, [`SeekableReaderBuilder::build`] works like
+    /// [`SeekableReader::new`] with a `keep_size` of 8 KiB.
+    #[cfg_attr(feature = "std", doc = " ```")]
+    #[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+    /// use std::io::{Read, Seek, SeekFrom};
+    /// use seekable_reader::{OutOfWindowPolicy, SeekableReader};
+    ///
+    /// let source: Vec<u8> = (0..100).collect();
+    /// let mut reader = SeekableReader::builder()
+    ///     .keep_size(8)
+    ///     .out_of_window(OutOfWindowPolicy::Clamp)
+    ///     .pin_head(4)
+    ///     .build(source.as_slice())
+    ///     .unwrap();
+    /// reader.read_exact(&mut [0; 50]).unwrap();
+    /// assert_eq!(reader.seek(SeekFrom::Start(10)).unwrap(), 34);
+    /// assert_eq!(reader.seek(SeekFrom::Start(2)).unwrap(), 2);
+    /// ```
+    pub fn builder() -> SeekableReaderBuilder<R> {
+        SeekableReaderBuilder {
+            keep_size: CHUNK_SIZE,
+            out_of_window: None,
+            reopen: None,
+            min_read: 0,
+            head_len: 0,
+            tail_len: 0,
+            stream_len: None,
+            eager: true,
+        }
+    }
+}
+
+impl<R: Read> SeekableReaderBuilder<R> {
+    /// Sets how many bytes are kept at least for seeking backwards, see [`SeekableReader::new`].
+    pub fn keep_size(mut self, keep_size: usize) -> Self {
+        self.keep_size = keep_size;
+        self
+    }
+
+    /// Sets what happens when seeking backwards to data that is no longer cached,
+    /// see [`SeekableReader::set_out_of_window_policy`].
+    ///
+    /// [`OutOfWindowPolicy::Reopen`] needs [`SeekableReaderBuilder::reopen`], and the other
+    /// policies don't go with it. Without this option, the policy is `Reopen` if
+    /// [`SeekableReaderBuilder::reopen`] is used, and [`OutOfWindowPolicy::Error`] otherwise.
+    pub fn out_of_window(mut self, policy: OutOfWindowPolicy) -> Self {
+        self.out_of_window = Some(policy);
+        self
+    }
+
+    /// Sets how to recreate `inner` at the start of the stream, see [`SeekableReader::with_reopen`].
+    ///
+    /// This implies the out-of-window policy [`OutOfWindowPolicy::Reopen`], and
+    /// [`SeekableReaderBuilder::build`] fails if another policy is set.
+    pub fn reopen<F>(mut self, reopen: F) -> Self
+    where
+        F: FnMut() -> Result<R> + Send + 'static,
+    {
+        self.reopen = Some(Reopen::Restart(Box::new(reopen)));
+        self
+    }
+
+    /// Makes reads from `inner` at least `min_read` bytes big, up to 8 KiB.
+    ///
+    /// Smaller reads are served from the cache, after reading `min_read` bytes into it.
+    /// This saves calls to inner readers which are slow per call, like unbuffered files.
+    pub fn min_read(mut self, min_read: usize) -> Self {
+        self.min_read = min_read;
+        self
+    }
+
+    /// Keeps the first `len` bytes of the stream for good, see [`SeekableReader::pin_head`].
+    pub fn pin_head(mut self, len: usize) -> Self {
+        self.head_len = len;
+        self
+    }
+
+    /// Keeps the last `len` bytes of the stream, see [`SeekableReader::pin_tail`].
+    pub fn pin_tail(mut self, len: usize) -> Self {
+        self.tail_len = len;
+        self
+    }
+
+    /// Tells the length of the stream, see [`SeekableReader::set_len_hint`].
+    pub fn stream_len(mut self, len: u64) -> Self {
+        self.stream_len = Some(len);
+        self
+    }
+
+    /// Sets whether the memory for the window is allocated up front, which is the default,
+    /// or as the data is read.
+    pub fn allocate_eagerly(mut self, eager: bool) -> Self {
+        self.eager = eager;
+        self
+    }
+
+    /// Creates the SeekableReader reading from `inner`.
+    ///
+    /// Fails with [`ErrorKind::InvalidInput`] if the options don't fit together.
+    pub fn build(self, inner: R) -> Result<SeekableReader<R>> {
+        self.validate()?;
+        let store = match self.eager {
+            true => RingStore::with_capacity(2 * self.keep_size),
+            false => RingStore::new(),
+        };
+        let mut reader = SeekableReader::with_store(inner, self.keep_size, store);
+        reader.window.out_of_window = self.policy();
+        reader.reopen = self.reopen;
+        reader.min_read = self.min_read;
+        if let Some(len) = self.stream_len {
+            reader.set_len_hint(len);
+        }
+        reader.pin_head(self.head_len)?;
+        reader.pin_tail(self.tail_len)?;
+        Ok(reader)
+    }
+
+    fn policy(&self) -> OutOfWindowPolicy {
+        match (self.out_of_window, &self.reopen) {
+            (Some(policy), _) => policy,
+            (None, Some(_)) => OutOfWindowPolicy::Reopen,
+            (None, None) => OutOfWindowPolicy::Error,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let invalid = |message| Err(Error::new(ErrorKind::InvalidInput, message));
+        let reopen_policy = self.policy() == OutOfWindowPolicy::Reopen;
+        if reopen_policy && self.reopen.is_none() {
+            return invalid("the reopen policy needs a way to recreate the inner reader");
+        }
+        if !reopen_policy && self.reopen.is_some() {
+            return invalid("a way to recreate the inner reader needs the reopen policy");
+        }
+        if self.min_read > CHUNK_SIZE {
+            return invalid("the minimum read size must not exceed 8 KiB");
+        }
+        if self.min_read > 2 * self.keep_size {
+            return invalid("the minimum read size must not exceed the window");
+        }
+        if self
+            .stream_len
+            .is_some_and(|len| self.head_len as u64 > len)
+        {
+            return invalid("the pinned head must not be longer than the stream");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{OutOfWindowPolicy, SeekableReader};
+    use std::io::{ErrorKind, Read, Seek, SeekFrom};
+
+    /// Counts the reads from the inner reader
+    struct Counted<'a>(&'a [u8], usize);
+
+    impl Read for Counted<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.1 += 1;
+            self.0.read(buf)
+        }
+    }
+
+    #[test]
+    fn min_read() {
+        let source: Vec<u8> = (0..100).collect();
+        let mut reader = SeekableReader::builder()
+            .keep_size(16)
+            .min_read(32)
+            .allocate_eagerly(false)
+            .build(Counted(&source, 0))
+            .unwrap();
+        let mut byte = [0];
+        for expected in 0..64 {
+            reader.read_exact(&mut byte).unwrap();
+            assert_eq!(byte, [expected]);
+        }
+        assert_eq!(reader.inner.1, 2);
+        reader.read_exact(&mut [0; 36]).unwrap();
+        assert_eq!(reader.inner.1, 3);
+    }
+
+    #[test]
+    fn stream_len_and_reopen() {
+        let source: Vec<u8> = (0..100).collect();
+        let open = {
+            let source = source.clone();
+            move || Ok(std::io::Cursor::new(source.clone()))
+        };
+        let mut reader = SeekableReader::builder()
+            .keep_size(4)
+            .reopen(open)
+            .stream_len(100)
+            .build(std::io::Cursor::new(source))
+            .unwrap();
+        assert_eq!(reader.seek(SeekFrom::End(-10)).unwrap(), 90);
+        assert_eq!(reader.read_bytes(), 90);
+        reader.read_exact(&mut [0; 10]).unwrap();
+        reader.seek(SeekFrom::Start(3)).unwrap();
+        assert_eq!(reader.reopen_count(), 1);
+    }
+
+    #[test]
+    fn invalid_options() {
+        let build = |builder: crate::SeekableReaderBuilder<&[u8]>| builder.build(&[][..]).err();
+        let cases = [
+            SeekableReader::builder().out_of_window(OutOfWindowPolicy::Reopen),
+            SeekableReader::builder()
+                .out_of_window(OutOfWindowPolicy::Clamp)
+                .reopen(|| Ok(&[][..])),
+            SeekableReader::builder()
+                .reopen(|| Ok(&[][..]))
+                .out_of_window(OutOfWindowPolicy::Clamp),
+            SeekableReader::builder().min_read(10_000),
+            SeekableReader::builder().keep_size(4).min_read(9),
+            SeekableReader::builder().stream_len(10).pin_head(11),
+        ];
+        for builder in cases {
+            assert_eq!(build(builder).unwrap().kind(), ErrorKind::InvalidInput);
+        }
+        assert!(build(SeekableReader::builder().keep_size(4).min_read(8)).is_none());
+        let reopen = SeekableReader::builder()
+            .out_of_window(OutOfWindowPolicy::Reopen)
+            .reopen(|| Ok(&[][..]));
+        assert!(build(reopen).is_none());
+    }
+}
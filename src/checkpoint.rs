@@ -0,0 +1,279 @@
+use crate::io::{Read, Result, SeekFrom};
+use crate::{CacheStore, RingStore, SeekableReader};
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::io::{BufRead, Seek};
+
+/// A stream position to return to, see [`SeekableReader::checkpoint`]
+///
+/// While the checkpoint is alive, the data after it stays cached, even if that
+/// is more than `2 * keep_size` bytes. The reader is used through the checkpoint.
+/// Dropping a checkpoint without committing it rolls it back.
+pub struct Checkpoint<'a, R: Read, S: CacheStore = RingStore> {
+    reader: &'a mut SeekableReader<R, S>,
+    position: u64,
+    /// The pin of an enclosing checkpoint, which is restored on drop
+    outer_pin: Option<u64>,
+    /// Whether the position is restored on drop
+    rollback: bool,
+}
+
+impl<R: Read, S: CacheStore> SeekableReader<R, S> {
+    /// Marks the stream position, so it can be returned to later.
+    ///
+    /// Checkpoints can be nested, by creating another checkpoint through the first.
+    #[cfg_attr(feature = "std", doc = " ```")]
+    #[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+    /// use std::io::Read;
+    /// use seekable_reader::SeekableReader;
+    ///
+    /// let source: Vec<u8> = (0..100).collect();
+    /// let mut reader = SeekableReader::new(source.as_slice(), 4);
+    /// let mut checkpoint = reader.checkpoint();
+    /// checkpoint.read_exact(&mut [0; 50]).unwrap();
+    /// checkpoint.rollback().unwrap();
+    /// let mut buffer = [0; 3];
+    /// reader.read_exact(&mut buffer).unwrap();
+    /// assert_eq!(buffer, [0, 1, 2]);
+    /// ```
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, R, S> {
+        let position = self.get_stream_position();
+        let outer_pin = self.window.pin();
+        Checkpoint {
+            reader: self,
+            position,
+            outer_pin,
+            rollback: true,
+        }
+    }
+
+    /// Runs `parse` on the reader, and returns to the stream position
+    /// if `parse` fails or panics.
+    ///
+    /// If `parse` fails and returning is impossible, like when a [`FixedStore`](crate::FixedStore)
+    /// could not hold the data `parse` read, the error of the rollback is returned instead.
+    ///
+    /// The data `parse` reads stays cached until it returns, like with [`SeekableReader::checkpoint`].
+    #[cfg_attr(feature = "std", doc = " ```")]
+    #[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+    /// use std::io::{Error, ErrorKind, Read};
+    /// use seekable_reader::SeekableReader;
+    ///
+    /// let source = b"RIFF....WAVE";
+    /// let mut reader = SeekableReader::new(&source[..], 4);
+    /// let mut expect_magic = |reader: &mut SeekableReader<_>, magic: &[u8]| {
+    ///     reader.try_parse(|reader| {
+    ///         let mut buffer = [0; 4];
+    ///         reader.read_exact(&mut buffer)?;
+    ///         match &buffer == magic {
+    ///             true => Ok(()),
+    ///             false => Err(Error::from(ErrorKind::InvalidData)),
+    ///         }
+    ///     })
+    /// };
+    /// assert!(expect_magic(&mut reader, b"OggS").is_err());
+    /// assert!(expect_magic(&mut reader, b"RIFF").is_ok());
+    /// assert_eq!(reader.get_stream_position(), 4);
+    /// ```
+    pub fn try_parse<T, F>(&mut self, parse: F) -> Result<T>
+    where
+        F: FnOnce(&mut SeekableReader<R, S>) -> Result<T>,
+    {
+        let mut checkpoint = self.checkpoint();
+        // On panics, dropping the checkpoint restores the position
+        match parse(&mut checkpoint) {
+            Ok(parsed) => {
+                checkpoint.commit();
+                Ok(parsed)
+            }
+            Err(err) => {
+                checkpoint.rollback()?;
+                Err(err)
+            }
+        }
+    }
+}
+
+impl<R: Read, S: CacheStore> Checkpoint<'_, R, S> {
+    /// Returns the stream position this checkpoint returns to.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns to the stream position of the checkpoint and releases the data after it.
+    pub fn rollback(mut self) -> Result<u64> {
+        self.rollback = false;
+        self.reader.seek_to(SeekFrom::Start(self.position))
+    }
+
+    /// Keeps the current stream position and releases the data after the checkpoint.
+    pub fn commit(mut self) {
+        self.rollback = false;
+    }
+}
+
+impl<R: Read, S: CacheStore> Drop for Checkpoint<'_, R, S> {
+    fn drop(&mut self) {
+        if self.rollback {
+            // This fails if the pinned data is gone, because a seek in the meantime made the
+            // reader recreate `inner`, or because the store could not hold it. There is no
+            // way to report that from here, rollback() and try_parse() report it instead.
+            let _ = self.reader.seek_to(SeekFrom::Start(self.position));
+        }
+        self.reader.window.unpin(self.outer_pin);
+    }
+}
+
+impl<R: Read, S: CacheStore> Deref for Checkpoint<'_, R, S> {
+    type Target = SeekableReader<R, S>;
+
+    fn deref(&self) -> &SeekableReader<R, S> {
+        self.reader
+    }
+}
+
+impl<R: Read, S: CacheStore> DerefMut for Checkpoint<'_, R, S> {
+    fn deref_mut(&mut self) -> &mut SeekableReader<R, S> {
+        self.reader
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read, S: CacheStore> Read for Checkpoint<'_, R, S> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read, S: CacheStore> BufRead for Checkpoint<'_, R, S> {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read, S: CacheStore> Seek for Checkpoint<'_, R, S> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.reader.seek(pos)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{ArrayStore, SeekOutOfWindow, SeekableReader};
+    use std::io::{Read, Seek, SeekFrom};
+
+    #[test]
+    fn rollback_beyond_window() {
+        let source: Vec<u8> = (0..=255).collect();
+        let mut reader = SeekableReader::new(source.as_slice(), 4);
+        reader.read_exact(&mut [0; 10]).unwrap();
+        let mut checkpoint = reader.checkpoint();
+        checkpoint.read_exact(&mut [0; 100]).unwrap();
+        assert_eq!(checkpoint.window_range(), 10..110);
+        assert_eq!(checkpoint.rollback().unwrap(), 10);
+        let mut buffer = [0; 2];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [10, 11]);
+        // The data is released again
+        reader.read_exact(&mut [0; 100]).unwrap();
+        assert_eq!(reader.buffered_size(), 8);
+    }
+
+    #[test]
+    fn commit_and_drop() {
+        let source: Vec<u8> = (0..=255).collect();
+        let mut reader = SeekableReader::new(source.as_slice(), 4);
+        let mut checkpoint = reader.checkpoint();
+        checkpoint.read_exact(&mut [0; 20]).unwrap();
+        checkpoint.commit();
+        assert_eq!(reader.get_stream_position(), 20);
+        {
+            let mut checkpoint = reader.checkpoint();
+            checkpoint.seek(SeekFrom::Current(30)).unwrap();
+        }
+        assert_eq!(reader.get_stream_position(), 20);
+    }
+
+    #[test]
+    fn nested() {
+        let source: Vec<u8> = (0..=255).collect();
+        let mut reader = SeekableReader::new(source.as_slice(), 2);
+        let mut outer = reader.checkpoint();
+        outer.read_exact(&mut [0; 10]).unwrap();
+        let mut inner = outer.checkpoint();
+        inner.read_exact(&mut [0; 50]).unwrap();
+        assert_eq!(inner.position(), 10);
+        inner.rollback().unwrap();
+        assert_eq!(outer.get_stream_position(), 10);
+        outer.read_exact(&mut [0; 50]).unwrap();
+        assert_eq!(outer.window_range(), 0..60);
+        outer.rollback().unwrap();
+        let mut buffer = [0; 2];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [0, 1]);
+    }
+
+    #[test]
+    fn try_parse() {
+        let source: Vec<u8> = (0..=255).collect();
+        let mut reader = SeekableReader::new(source.as_slice(), 2);
+        let failed: std::io::Result<()> = reader.try_parse(|reader| {
+            reader.read_exact(&mut [0; 40])?;
+            Err(std::io::ErrorKind::InvalidData.into())
+        });
+        assert!(failed.is_err());
+        assert_eq!(reader.get_stream_position(), 0);
+        let parsed = reader.try_parse(|reader| {
+            let mut buffer = [0; 2];
+            reader.read_exact(&mut buffer)?;
+            // A failing alternative inside does not affect the outer parser
+            let inner: std::io::Result<u8> = reader.try_parse(|reader| {
+                reader.read_exact(&mut [0; 30])?;
+                Err(std::io::ErrorKind::InvalidData.into())
+            });
+            assert!(inner.is_err());
+            reader.read_exact(&mut buffer[1..])?;
+            Ok(buffer)
+        });
+        assert_eq!(parsed.unwrap(), [0, 2]);
+        assert_eq!(reader.get_stream_position(), 3);
+    }
+
+    #[test]
+    fn try_parse_in_small_store() {
+        let source: Vec<u8> = (0..=255).collect();
+        let mut reader = SeekableReader::<_, ArrayStore<8>>::with_array(source.as_slice());
+        let failed: std::io::Result<()> = reader.try_parse(|reader| {
+            reader.read_exact(&mut [0; 20])?;
+            Err(std::io::ErrorKind::InvalidData.into())
+        });
+        // The rollback failed, which is reported instead of the error of the parser
+        let err = failed.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(err.get_ref().unwrap().is::<SeekOutOfWindow>());
+    }
+
+    #[test]
+    fn try_parse_panics() {
+        let source: Vec<u8> = (0..=255).collect();
+        let mut reader = SeekableReader::new(source.as_slice(), 2);
+        reader.read_exact(&mut [0; 5]).unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            reader.try_parse(|reader| -> std::io::Result<()> {
+                reader.read_exact(&mut [0; 50])?;
+                panic!("parser bug");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(reader.get_stream_position(), 5);
+        let mut buffer = [0; 1];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [5]);
+    }
+}
@@ -0,0 +1,131 @@
+//! The [`embedded_io`] traits, for the SeekableReader to be used by embedded-io code.
+use crate::io::{self, Read};
+use crate::{CacheStore, SeekableReader};
+
+impl<R: Read, S: CacheStore> embedded_io::ErrorType for SeekableReader<R, S> {
+    type Error = io::Error;
+}
+
+impl<R: Read, S: CacheStore> embedded_io::Read for SeekableReader<R, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_into(buf)
+    }
+}
+
+impl<R: Read, S: CacheStore> embedded_io::BufRead for SeekableReader<R, S> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.fill()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.window.consume(amt);
+    }
+}
+
+impl<R: Read, S: CacheStore> embedded_io::Seek for SeekableReader<R, S> {
+    // Without std, the crate uses the SeekFrom of embedded-io itself
+    #[allow(clippy::useless_conversion)]
+    fn seek(&mut self, pos: embedded_io::SeekFrom) -> io::Result<u64> {
+        self.seek_to(pos.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SeekableReader;
+    use alloc::vec::Vec;
+    use embedded_io::{BufRead, ErrorKind, Read, Seek, SeekFrom};
+
+    #[test]
+    fn embedded_io_traits() {
+        let source: Vec<u8> = (0..100).collect();
+        let mut reader = SeekableReader::new(source.as_slice(), 10);
+        reader.read_exact(&mut [0; 50]).unwrap();
+        assert_eq!(reader.seek(SeekFrom::Current(-5)).unwrap(), 45);
+        let mut buffer = [0; 3];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [45, 46, 47]);
+        assert_eq!(reader.fill_buf().unwrap()[0], 48);
+        reader.consume(2);
+        assert_eq!(reader.stream_position().unwrap(), 50);
+        let err = reader.seek(SeekFrom::Start(0)).unwrap_err();
+        assert_eq!(embedded_io::Error::kind(&err), ErrorKind::InvalidInput);
+    }
+
+    /// A seekable embedded-io reader, which fails at `fail_at`
+    #[cfg(not(feature = "std"))]
+    struct Cursor {
+        data: Vec<u8>,
+        pos: usize,
+        fail_at: usize,
+    }
+
+    #[cfg(not(feature = "std"))]
+    impl embedded_io::ErrorType for Cursor {
+        type Error = ErrorKind;
+    }
+
+    #[cfg(not(feature = "std"))]
+    impl Read for Cursor {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, ErrorKind> {
+            if self.pos == self.fail_at {
+                return Err(ErrorKind::BrokenPipe);
+            }
+            let end = self.fail_at.min(self.data.len());
+            let data = &self.data[self.pos.min(end)..end];
+            let len = data.len().min(buf.len());
+            buf[..len].copy_from_slice(&data[..len]);
+            self.pos += len;
+            Ok(len)
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    impl Seek for Cursor {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64, ErrorKind> {
+            self.pos = match pos {
+                SeekFrom::Start(offset) => offset as usize,
+                SeekFrom::End(shift) => (self.data.len() as i64 + shift) as usize,
+                SeekFrom::Current(shift) => (self.pos as i64 + shift) as usize,
+            };
+            Ok(self.pos as u64)
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[test]
+    fn embedded_io_inner() {
+        let inner = Cursor {
+            data: (0..100).collect(),
+            pos: 0,
+            fail_at: 60,
+        };
+        let mut reader = SeekableReader::with_seekable_inner(inner, 4).unwrap();
+        assert_eq!(reader.stream_len(), Some(100));
+        reader.read_exact(&mut [0; 50]).unwrap();
+        // Seeking back out of the window seeks `inner`
+        reader.seek(SeekFrom::Start(10)).unwrap();
+        let mut buffer = [0; 2];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [10, 11]);
+        assert_eq!(reader.reopen_count(), 1);
+        // Errors of `inner` keep their kind
+        reader.seek(SeekFrom::Start(58)).unwrap();
+        assert_eq!(Read::read(&mut reader, &mut [0; 4]).unwrap(), 2);
+        let err = Read::read(&mut reader, &mut [0; 4]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+    }
+
+    #[cfg(not(feature = "std"))]
+    #[test]
+    fn out_of_window_error() {
+        let source: Vec<u8> = (0..100).collect();
+        let mut reader = SeekableReader::new(source.as_slice(), 4);
+        reader.read_exact(&mut [0; 50]).unwrap();
+        let err = reader.seek(SeekFrom::Start(0)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(err.out_of_window().unwrap().earliest_available, 42);
+        let err = reader.peek_exact(60).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+    }
+}
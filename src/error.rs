@@ -0,0 +1,96 @@
+use crate::io;
+use core::error::Error;
+use core::fmt;
+
+/// The target of a seek is no longer held by the cache.
+///
+/// It is returned inside an [`io::Error`] of kind [`io::ErrorKind::InvalidInput`]
+/// and can be inspected by downcasting, or through `Error::out_of_window` without the `std` feature:
+#[cfg_attr(feature = "std", doc = " ```")]
+#[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+/// use std::io::{Read, Seek, SeekFrom};
+/// use seekable_reader::{SeekOutOfWindow, SeekableReader};
+///
+/// let source: Vec<u8> = (0..100).collect();
+/// let mut reader = SeekableReader::new(source.as_slice(), 10);
+/// reader.read_exact(&mut [0; 50]).unwrap();
+/// let err = reader.seek(SeekFrom::Start(0)).unwrap_err();
+/// let err = err.get_ref().unwrap().downcast_ref::<SeekOutOfWindow>().unwrap();
+/// assert_eq!(err.earliest_available, 30);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeekOutOfWindow {
+    /// The stream position that was requested
+    pub requested: u64,
+    /// The earliest stream position that is still cached
+    pub earliest_available: u64,
+}
+
+impl fmt::Display for SeekOutOfWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cannot seek to position {}, the earliest cached position is {}",
+            self.requested, self.earliest_available
+        )
+    }
+}
+
+impl Error for SeekOutOfWindow {}
+
+#[cfg(feature = "std")]
+impl From<SeekOutOfWindow> for io::Error {
+    fn from(err: SeekOutOfWindow) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
+
+/// The error for seeks before the start of the stream
+pub(crate) fn negative_seek() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "invalid seek to a negative position",
+    )
+}
+
+/// The error for seeks beyond the expected length of the stream
+pub(crate) fn seek_beyond_len() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "invalid seek beyond the expected length of the stream",
+    )
+}
+
+/// The error for peeking further than the store can hold
+pub(crate) fn peek_beyond_capacity() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "cannot peek further than the store can hold",
+    )
+}
+
+/// The error for pinning a tail longer than the store can hold
+pub(crate) fn tail_beyond_capacity() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "cannot pin a tail longer than the store can hold",
+    )
+}
+
+/// The error for a stream which is longer than its expected length
+pub(crate) fn beyond_len_hint() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "the stream is longer than its expected length",
+    )
+}
+
+/// The error for a stream which ended too early
+pub(crate) fn unexpected_eof(message: &'static str) -> io::Error {
+    // embedded-io reports this outside of its error kinds
+    #[cfg(not(feature = "std"))]
+    let kind = io::ErrorKind::Other;
+    #[cfg(feature = "std")]
+    let kind = io::ErrorKind::UnexpectedEof;
+    io::Error::new(kind, message)
+}
@@ -0,0 +1,118 @@
+//! The I/O types the crate is built on.
+//!
+//! With the `std` feature, these are the ones of `std::io`. Without it, the crate reads
+//! through the traits of [`embedded_io`], and this module provides stand-ins for the parts
+//! of `std::io` it needs. Every [`embedded_io::Read`] is a [`Read`] here, and every
+//! [`embedded_io::Seek`] a [`Seek`], so any embedded-io reader can be wrapped.
+#[cfg(feature = "std")]
+pub use std::io::{BufRead, Error, ErrorKind, Read, Result, Seek, SeekFrom};
+
+#[cfg(not(feature = "std"))]
+pub use self::no_std::*;
+
+#[cfg(not(feature = "std"))]
+mod no_std {
+    use crate::SeekOutOfWindow;
+    use core::fmt;
+    pub use embedded_io::{ErrorKind, SeekFrom};
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// The error type of the crate without the `std` feature, in place of `std::io::Error`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Error {
+        kind: ErrorKind,
+        cause: Cause,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Cause {
+        /// An error of the inner reader, of which only the kind is known
+        Kind,
+        Message(&'static str),
+        OutOfWindow(SeekOutOfWindow),
+    }
+
+    impl Error {
+        pub fn new(kind: ErrorKind, message: &'static str) -> Error {
+            Error {
+                kind,
+                cause: Cause::Message(message),
+            }
+        }
+
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+
+        /// Returns the details of the error, if a seek failed because its target is no longer cached.
+        pub fn out_of_window(&self) -> Option<&SeekOutOfWindow> {
+            match &self.cause {
+                Cause::OutOfWindow(err) => Some(err),
+                _ => None,
+            }
+        }
+    }
+
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Error {
+            Error {
+                kind,
+                cause: Cause::Kind,
+            }
+        }
+    }
+
+    impl From<SeekOutOfWindow> for Error {
+        fn from(err: SeekOutOfWindow) -> Error {
+            Error {
+                kind: ErrorKind::InvalidInput,
+                cause: Cause::OutOfWindow(err),
+            }
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match &self.cause {
+                Cause::Kind => write!(f, "{:?}", self.kind),
+                Cause::Message(message) => f.write_str(message),
+                Cause::OutOfWindow(err) => err.fmt(f),
+            }
+        }
+    }
+
+    impl core::error::Error for Error {}
+
+    impl embedded_io::Error for Error {
+        fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    /// The reading half of `std::io::Read`
+    pub trait Read {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+    }
+
+    impl<T: embedded_io::Read + ?Sized> Read for T {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            embedded_io::Read::read(self, buf).map_err(|err| embedded_io::Error::kind(&err).into())
+        }
+    }
+
+    /// The seeking half of `std::io::Seek`
+    pub trait Seek {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64>;
+
+        fn stream_position(&mut self) -> Result<u64> {
+            self.seek(SeekFrom::Current(0))
+        }
+    }
+
+    impl<T: embedded_io::Seek + ?Sized> Seek for T {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            embedded_io::Seek::seek(self, pos).map_err(|err| embedded_io::Error::kind(&err).into())
+        }
+    }
+}
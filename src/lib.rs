@@ -1,29 +1,101 @@
-///
-/// This crate introduces the **SeekableReader**, which provides `Seek` if wrapped around a `Read` instance.
-///
-/// An example:
-///  ```
-/// use std::io::{Read, Seek, SeekFrom};
-/// use seekable_reader::SeekableReader;
-///
-/// let source = vec![1, 2, 3, 4, 5];
-/// let mut reader = SeekableReader::new(source.as_slice(), 1);
-/// let mut buffer = vec![0; 5];
-/// // Read one byte and seek back
-/// reader.read(&mut buffer[..1]).unwrap();
-/// reader.seek(SeekFrom::Start(0)).unwrap();
-/// // First byte can be read again!
-/// let bytes: Vec<_> = reader.bytes().map(|b| b.unwrap()).collect();
-/// assert_eq!(&source, &bytes);
-/// ```
-use core::cmp::min;
-use std::io::{Read, Result, Seek, SeekFrom};
-use std::mem;
-
-#[derive(Debug)]
-enum Position {
-    FrontBuffer(usize),
-    BackBuffer(usize),
+#![cfg_attr(not(feature = "std"), no_std)]
+//! This crate introduces the **SeekableReader**, which provides `Seek` if wrapped around a `Read` instance.
+//!
+//! An example:
+#![cfg_attr(feature = "std", doc = " ```")]
+#![cfg_attr(not(feature = "std"), doc = " ```ignore")]
+//! use std::io::{Read, Seek, SeekFrom};
+//! use seekable_reader::SeekableReader;
+//!
+//! let source = vec![1, 2, 3, 4, 5];
+//! let mut reader = SeekableReader::new(source.as_slice(), 1);
+//! let mut buffer = vec![0; 5];
+//! // Read one byte and seek back
+//! reader.read(&mut buffer[..1]).unwrap();
+//! reader.seek(SeekFrom::Start(0)).unwrap();
+//! // First byte can be read again!
+//! let bytes: Vec<_> = reader.bytes().map(|b| b.unwrap()).collect();
+//! assert_eq!(&source, &bytes);
+//! ```
+
+#[cfg(not(any(feature = "std", feature = "embedded-io")))]
+compile_error!("seekable_reader needs either the `std` or the `embedded-io` feature");
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use core::cmp::{max, min};
+use core::ops::Range;
+#[cfg(feature = "std")]
+use io::BufRead;
+use io::{Read, Result, Seek, SeekFrom};
+
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod async_reader;
+mod builder;
+mod checkpoint;
+#[cfg(feature = "embedded-io")]
+mod embedded;
+mod error;
+#[cfg(feature = "std")]
+mod io;
+#[cfg(not(feature = "std"))]
+pub mod io;
+#[cfg(feature = "std")]
+mod prefetch;
+mod range;
+#[cfg(feature = "std")]
+mod shared;
+pub mod store;
+mod window;
+
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+pub use async_reader::AsyncSeekableReader;
+pub use builder::SeekableReaderBuilder;
+pub use checkpoint::Checkpoint;
+pub use error::SeekOutOfWindow;
+#[cfg(feature = "std")]
+pub use prefetch::Prefetch;
+#[cfg(feature = "std")]
+pub use range::FileRangeSource;
+pub use range::RangeSource;
+#[cfg(feature = "std")]
+pub use shared::{SharedCursor, SharedSeekableSource};
+#[cfg(feature = "mmap")]
+pub use store::MmapStore;
+#[cfg(feature = "tempfile")]
+pub use store::TempFileStore;
+pub use store::{ArrayStore, BlockStore, CacheStore, FixedStore, RingStore, SliceStore, VecStore};
+use window::Window;
+
+/// Upper limit for reads from `inner` the user did not ask for directly
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Forward seeks further than this, and the window, recreate `inner` if possible
+const FAR_SEEK: u64 = 64 * 1024;
+
+/// How `inner` can be recreated to reach data that is not cached
+enum Reopen<R> {
+    /// Recreates `inner` at the start of the stream
+    Restart(Box<dyn FnMut() -> Result<R> + Send>),
+    /// Recreates `inner` at any stream position
+    At(Box<dyn FnMut(u64) -> Result<R> + Send>),
+    /// Seeks `inner` itself to any stream position
+    Seek(fn(&mut R, u64) -> Result<()>),
+}
+
+/// What to do when seeking to a position that is no longer cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutOfWindowPolicy {
+    /// Fail with a [`SeekOutOfWindow`] error and leave the stream position untouched
+    #[default]
+    Error,
+    /// Stop at the earliest cached position
+    Clamp,
+    /// Recreate `inner` and read it up to the target, see [`SeekableReader::with_reopen`]
+    ///
+    /// Readers which cannot recreate `inner` fail like with `Error`.
+    Reopen,
 }
 
 /// A reader adapter that allows to seek a little bit
@@ -31,16 +103,15 @@ enum Position {
 /// The SeekableReader will wrap around a Read instance and can be read normally.
 /// The core feature is to provide `Seek`, even if the underlying Reader does not.
 /// It achieves this by holding a cache of the read data, which can be read again.
-pub struct SeekableReader<R: Read> {
+///
+/// The cache lives in a [`CacheStore`], which is a [`RingStore`] by default.
+pub struct SeekableReader<R: Read, S: CacheStore = RingStore> {
     pub inner: R,
-    pub keep_size: usize,
-    // TODO migrate to arrayvec
-    current_buffer: Vec<u8>,
-    older_buffer: Vec<u8>,
-    pos: Position,
-    /// Bytes read from `inner`
-    pub read_bytes: usize,
-    buffer_begins_at_pos: usize,
+    window: Window<S>,
+    reopen: Option<Reopen<R>>,
+    reopen_count: usize,
+    /// Reads from `inner` smaller than this are made this big, see [`SeekableReaderBuilder::min_read`]
+    min_read: usize,
 }
 
 impl<R: Read> SeekableReader<R> {
@@ -52,122 +123,550 @@ impl<R: Read> SeekableReader<R> {
     ///
     /// At most, `2 * keep_size` bytes are kept.
     pub fn new(inner: R, keep_size: usize) -> SeekableReader<R> {
-        SeekableReader {
+        SeekableReader::with_store(inner, keep_size, RingStore::with_capacity(2 * keep_size))
+    }
+
+    /// Create a new SeekableReader for a stream whose length `len` is known, but not told by `inner`.
+    ///
+    /// See [`SeekableReader::set_len_hint`]. Everything else works like with [`SeekableReader::new`].
+    #[cfg_attr(feature = "std", doc = " ```")]
+    #[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+    /// use std::io::{ErrorKind, Read, Seek, SeekFrom};
+    /// use seekable_reader::SeekableReader;
+    ///
+    /// let source: Vec<u8> = (0..100).collect();
+    /// let mut reader = SeekableReader::with_len(source.as_slice(), 16, 120);
+    /// assert_eq!(reader.seek(SeekFrom::End(-30)).unwrap(), 90);
+    /// // The stream is shorter than expected
+    /// let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+    /// assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    /// ```
+    pub fn with_len(inner: R, keep_size: usize, len: u64) -> SeekableReader<R> {
+        let mut reader = SeekableReader::new(inner, keep_size);
+        reader.set_len_hint(len);
+        reader
+    }
+
+    /// Create a new SeekableReader for streams which can be restarted, but not seeked.
+    ///
+    /// `reopen` is called once to create `inner`. When seeking backwards to data that is no longer
+    /// cached, it is called again, and the new `inner` is read up to the target of the seek.
+    /// Everything else works like with [`SeekableReader::new`].
+    #[cfg_attr(feature = "std", doc = " ```")]
+    #[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+    /// use std::io::{Cursor, Read, Seek, SeekFrom};
+    /// use seekable_reader::SeekableReader;
+    ///
+    /// let source: Vec<u8> = (0..100).collect();
+    /// let open = move || Ok(Cursor::new(source.clone()));
+    /// let mut reader = SeekableReader::with_reopen(open, 10).unwrap();
+    /// reader.read_exact(&mut [0; 50]).unwrap();
+    /// reader.seek(SeekFrom::Start(5)).unwrap();
+    /// let mut buffer = [0; 3];
+    /// reader.read_exact(&mut buffer).unwrap();
+    /// assert_eq!(buffer, [5, 6, 7]);
+    /// assert_eq!(reader.reopen_count(), 1);
+    /// ```
+    pub fn with_reopen<F>(mut reopen: F, keep_size: usize) -> Result<SeekableReader<R>>
+    where
+        F: FnMut() -> Result<R> + Send + 'static,
+    {
+        let mut reader = SeekableReader::new(reopen()?, keep_size);
+        reader.reopen = Some(Reopen::Restart(Box::new(reopen)));
+        reader.window.out_of_window = OutOfWindowPolicy::Reopen;
+        Ok(reader)
+    }
+
+    /// Create a new SeekableReader which reads from a [`RangeSource`].
+    ///
+    /// Seeking backwards to data that is no longer cached, and seeking far ahead,
+    /// opens a new reader at the target of the seek, instead of reading up to it.
+    /// If the source knows its length, seeking relative to the end does not read the stream.
+    /// Everything else works like with [`SeekableReader::new`].
+    #[cfg_attr(feature = "std", doc = " ```")]
+    #[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+    /// # let file = tempfile::NamedTempFile::new().unwrap();
+    /// # let path = file.path();
+    /// # std::fs::write(path, (0..=255).collect::<Vec<u8>>()).unwrap();
+    /// use std::io::{Read, Seek, SeekFrom};
+    /// use seekable_reader::{FileRangeSource, SeekableReader};
+    ///
+    /// let source = FileRangeSource::new(path).unwrap();
+    /// let mut reader = SeekableReader::with_range_source(source, 16).unwrap();
+    /// reader.seek(SeekFrom::End(-3)).unwrap();
+    /// let mut buffer = [0; 3];
+    /// reader.read_exact(&mut buffer).unwrap();
+    /// assert_eq!(buffer, [253, 254, 255]);
+    /// assert_eq!(reader.read_bytes(), 256);
+    /// ```
+    pub fn with_range_source<T>(mut source: T, keep_size: usize) -> Result<SeekableReader<R>>
+    where
+        T: RangeSource<Reader = R> + Send + 'static,
+    {
+        let mut reader = SeekableReader::new(source.open_at(0)?, keep_size);
+        if let Some(len) = source.len() {
+            reader.window.set_stream_len(len);
+        }
+        reader.reopen = Some(Reopen::At(Box::new(move |offset| source.open_at(offset))));
+        reader.window.out_of_window = OutOfWindowPolicy::Reopen;
+        Ok(reader)
+    }
+}
+
+impl<R: Read + Seek> SeekableReader<R> {
+    /// Create a new SeekableReader for readers which may be seekable themselves.
+    ///
+    /// If `inner` supports seeking, seeks to data that is not cached, and seeks far ahead,
+    /// are passed on to `inner`, and the cache starts over at the target of the seek.
+    /// The stream positions are those of `inner`, and seeking relative to the end does not
+    /// read the stream. This suits files as well as `File`s that are actually pipes:
+    /// if seeking `inner` fails, it is only read, just like with [`SeekableReader::new`].
+    #[cfg_attr(feature = "std", doc = " ```")]
+    #[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+    /// use std::io::{Cursor, Read, Seek, SeekFrom};
+    /// use seekable_reader::SeekableReader;
+    ///
+    /// let source: Vec<u8> = (0..100).collect();
+    /// let mut reader = SeekableReader::with_seekable_inner(Cursor::new(source), 10).unwrap();
+    /// reader.read_exact(&mut [0; 50]).unwrap();
+    /// reader.seek(SeekFrom::Start(5)).unwrap();
+    /// let mut buffer = [0; 3];
+    /// reader.read_exact(&mut buffer).unwrap();
+    /// assert_eq!(buffer, [5, 6, 7]);
+    /// assert_eq!(reader.inner.position(), 8);
+    /// ```
+    pub fn with_seekable_inner(mut inner: R, keep_size: usize) -> Result<SeekableReader<R>> {
+        let seekable = stream_bounds(&mut inner);
+        let mut reader = SeekableReader::new(inner, keep_size);
+        if let Ok((position, len)) = seekable {
+            reader.window.reset(position)?;
+            reader.window.set_stream_len(len);
+            reader.reopen = Some(Reopen::Seek(|inner, offset| {
+                inner.seek(SeekFrom::Start(offset)).map(drop)
+            }));
+            reader.window.out_of_window = OutOfWindowPolicy::Reopen;
+        }
+        Ok(reader)
+    }
+}
+
+/// Calls `f` with a buffer of `len` bytes on the stack, but not more than 8 KiB.
+///
+/// The buffer has one of a few sizes, the smallest that fits, so that reading into a small
+/// [`FixedStore`] does not take 8 KiB of stack.
+fn with_chunk<T>(len: usize, f: impl FnOnce(&mut [u8]) -> T) -> T {
+    match len {
+        0..=64 => chunk_on_stack::<64, T>(len, f),
+        65..=512 => chunk_on_stack::<512, T>(len, f),
+        _ => chunk_on_stack::<CHUNK_SIZE, T>(len, f),
+    }
+}
+
+// Not inlined, so only the buffer of the chosen size is on the stack
+#[inline(never)]
+fn chunk_on_stack<const N: usize, T>(len: usize, f: impl FnOnce(&mut [u8]) -> T) -> T {
+    let mut chunk = [0; N];
+    f(&mut chunk[..min(len, N)])
+}
+
+/// Returns the stream position and length of `inner`, failing if it cannot seek.
+fn stream_bounds<R: Seek>(inner: &mut R) -> Result<(u64, u64)> {
+    let position = inner.stream_position()?;
+    let len = inner.seek(SeekFrom::End(0))?;
+    inner.seek(SeekFrom::Start(position))?;
+    Ok((position, len))
+}
+
+#[cfg(feature = "tempfile")]
+impl<R: Read> SeekableReader<R, TempFileStore> {
+    /// Create a new SeekableReader that spills old data into a temporary file.
+    ///
+    /// The last `2 * keep_size` bytes are kept in memory, just like with [`SeekableReader::new`].
+    /// Everything older is written to an anonymous temporary file instead of being discarded,
+    /// so seeking backwards can reach every position since the start of the stream.
+    ///
+    /// The file is removed when the SeekableReader is dropped.
+    pub fn with_spill(inner: R, keep_size: usize) -> Result<SeekableReader<R, TempFileStore>> {
+        Ok(SeekableReader::with_store(
             inner,
             keep_size,
-            current_buffer: Vec::with_capacity(keep_size),
-            older_buffer: Vec::with_capacity(keep_size),
-            pos: Position::FrontBuffer(0),
-            read_bytes: 0,
-            buffer_begins_at_pos: 0,
+            TempFileStore::new()?,
+        ))
+    }
+}
+
+impl<'a, R: Read> SeekableReader<R, SliceStore<'a>> {
+    /// Create a new SeekableReader which keeps its cache in `buffer` and never allocates.
+    ///
+    /// Half of the buffer is kept at least for seeking backwards. See [`FixedStore`]
+    /// for the few options that allocate anyway, like [`SeekableReader::pin_head`].
+    /// Everything else works like with [`SeekableReader::new`].
+    #[cfg_attr(feature = "std", doc = " ```")]
+    #[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+    /// use std::io::{Read, Seek, SeekFrom};
+    /// use seekable_reader::SeekableReader;
+    ///
+    /// let source: Vec<u8> = (0..100).collect();
+    /// let mut buffer = [0; 16];
+    /// let mut reader = SeekableReader::with_buffer(source.as_slice(), &mut buffer);
+    /// reader.seek(SeekFrom::Start(60)).unwrap();
+    /// reader.seek(SeekFrom::Start(52)).unwrap();
+    /// let mut byte = [0];
+    /// reader.read_exact(&mut byte).unwrap();
+    /// assert_eq!(byte, [52]);
+    /// ```
+    pub const fn with_buffer(inner: R, buffer: &'a mut [u8]) -> SeekableReader<R, SliceStore<'a>> {
+        let keep_size = buffer.len() / 2;
+        SeekableReader::with_store(inner, keep_size, SliceStore::new(buffer))
+    }
+}
+
+impl<R: Read, const N: usize> SeekableReader<R, ArrayStore<N>> {
+    /// Create a new SeekableReader which keeps its cache in an array of `N` bytes and never allocates.
+    ///
+    /// Half of the array is kept at least for seeking backwards. See [`FixedStore`]
+    /// for the few options that allocate anyway, like [`SeekableReader::pin_head`].
+    /// Everything else works like with [`SeekableReader::new`].
+    pub const fn with_array(inner: R) -> SeekableReader<R, ArrayStore<N>> {
+        SeekableReader::with_store(inner, N / 2, ArrayStore::new())
+    }
+}
+
+impl<R: Read, S: CacheStore> SeekableReader<R, S> {
+    /// Create a new SeekableReader which keeps its cache in `store`.
+    ///
+    /// The reader tells the store to drop everything older than the last `2 * keep_size` bytes.
+    /// Whether the store actually discards that data depends on the store.
+    pub const fn with_store(inner: R, keep_size: usize, store: S) -> SeekableReader<R, S> {
+        SeekableReader {
+            inner,
+            window: Window::new(keep_size, store),
+            reopen: None,
+            reopen_count: 0,
+            min_read: 0,
         }
     }
 
-    // Returns the number of bytes which can be read from inner before the next buffer swap.
-    fn remaining_current_buffer_capacity(&self) -> usize {
-        self.keep_size - self.current_buffer.len()
+    /// Keeps the first `len` bytes of the stream for good, besides the data kept for seeking backwards.
+    ///
+    /// Seeking to these bytes always succeeds, which helps with formats whose headers are
+    /// read again and again. Reading on beyond them works like seeking to the next byte.
+    /// This should be called before reading, unless `inner` can be recreated.
+    /// The head is copied into a buffer of its own, which is allocated once.
+    #[cfg_attr(feature = "std", doc = " ```")]
+    #[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+    /// use std::io::{Read, Seek, SeekFrom};
+    /// use seekable_reader::SeekableReader;
+    ///
+    /// let source: Vec<u8> = (0..100).collect();
+    /// let mut reader = SeekableReader::new(source.as_slice(), 4);
+    /// reader.pin_head(16).unwrap();
+    /// reader.read_exact(&mut [0; 80]).unwrap();
+    /// reader.seek(SeekFrom::Start(12)).unwrap();
+    /// let mut buffer = [0; 4];
+    /// reader.read_exact(&mut buffer).unwrap();
+    /// assert_eq!(buffer, [12, 13, 14, 15]);
+    /// assert!(reader.read_exact(&mut buffer).is_err());
+    /// ```
+    pub fn pin_head(&mut self, len: usize) -> Result<()> {
+        self.window.set_head_len(len)
+    }
+
+    /// Keeps the last `len` bytes of the stream once they were read, besides the data kept for seeking backwards.
+    ///
+    /// Seeking relative to the end by up to `len` bytes then always succeeds, which suits formats
+    /// whose index or trailer is at the end, like ZIP. Until the stream length is known,
+    /// the last `len` bytes read so far are kept, since they may turn out to be the tail.
+    /// Fails with [`ErrorKind::InvalidInput`](io::ErrorKind::InvalidInput) if the store
+    /// cannot hold `len` bytes.
+    #[cfg_attr(feature = "std", doc = " ```")]
+    #[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+    /// use std::io::{Read, Seek, SeekFrom};
+    /// use seekable_reader::SeekableReader;
+    ///
+    /// let source: Vec<u8> = (0..=255).collect();
+    /// let mut reader = SeekableReader::new(source.as_slice(), 4);
+    /// reader.pin_head(4).unwrap();
+    /// reader.pin_tail(22).unwrap();
+    /// reader.seek(SeekFrom::End(-22)).unwrap();
+    /// reader.read_exact(&mut [0; 22]).unwrap();
+    /// reader.seek(SeekFrom::Start(0)).unwrap();
+    /// reader.read_exact(&mut [0; 4]).unwrap();
+    /// reader.seek(SeekFrom::End(-20)).unwrap();
+    /// let mut buffer = [0; 2];
+    /// reader.read_exact(&mut buffer).unwrap();
+    /// assert_eq!(buffer, [236, 237]);
+    /// ```
+    pub fn pin_tail(&mut self, len: usize) -> Result<()> {
+        self.window.set_tail_len(len)
+    }
+
+    /// Sets what happens when seeking backwards to data that is no longer cached.
+    ///
+    /// By default, such seeks fail with a [`SeekOutOfWindow`] error.
+    pub fn set_out_of_window_policy(&mut self, policy: OutOfWindowPolicy) {
+        self.window.out_of_window = policy;
+    }
+
+    /// Returns how many bytes are kept at least for seeking backwards.
+    pub fn keep_size(&self) -> usize {
+        self.window.keep_size
+    }
+
+    /// Returns the stream position of `inner`, which is behind all data read so far.
+    pub fn read_bytes(&self) -> u64 {
+        self.window.read_bytes()
+    }
+
+    /// Returns the stream positions that are cached and can be seeked to without reading `inner`.
+    ///
+    /// With a [`BlockStore`], parts of this range may have been evicted.
+    pub fn window_range(&self) -> Range<u64> {
+        self.window.range()
+    }
+
+    /// Returns a reference to the store holding the cached data.
+    pub fn store(&self) -> &S {
+        self.window.store()
+    }
+
+    /// Returns how often `inner` was recreated or seeked, see [`SeekableReader::with_reopen`].
+    pub fn reopen_count(&self) -> usize {
+        self.reopen_count
     }
 
     /// Returns the size of the buffered data.
     /// Attempts to seek further back will result an Error.
     pub fn buffered_size(&self) -> usize {
-        self.current_buffer.len() + self.older_buffer.len()
+        self.store().len() as usize
     }
 
     /// Reads more data from `inner` into `buf` and puts them into the cache
     ///
     /// After this operation, the stream position will be at the end of all read data.
-    ///
-    /// If buf is long enough, the caches will be flushed.
     fn read_inner(&mut self, buf: &mut [u8]) -> Result<usize> {
         let read_bytes = self.inner.read(buf)?;
-        let buf = &mut buf[..read_bytes];
-        let cache_capacity = 2 * self.keep_size;
-        if read_bytes >= cache_capacity - self.current_buffer.len() {
-            // Flush cache and read everything out of the buffer
-            let skip = cache_capacity * (read_bytes % cache_capacity);
-            let (to_older, to_current) =
-                (&buf[skip..]).split_at(min(self.keep_size, buf.len() - skip));
-            self.older_buffer.resize(self.keep_size, 0);
-            self.older_buffer.as_mut_slice().copy_from_slice(to_older);
-            self.current_buffer.resize(to_current.len(), 0);
-            self.current_buffer.copy_from_slice(to_current);
-        } else if read_bytes > self.remaining_current_buffer_capacity() {
-            let to_older_size = self.remaining_current_buffer_capacity();
-            mem::swap(&mut self.older_buffer, &mut self.current_buffer);
-            let (to_older, to_current) = buf.split_at(min(to_older_size, buf.len()));
-            self.older_buffer.extend_from_slice(to_older);
-            self.current_buffer.resize(to_current.len(), 0);
-            self.current_buffer.copy_from_slice(to_current);
-        } else {
-            self.current_buffer.extend_from_slice(buf);
+        self.window.append_consumed(&buf[..read_bytes], buf.len())?;
+        Ok(read_bytes)
+    }
+
+    /// Reads `inner` in bounded chunks until `target` or EOF is reached.
+    ///
+    /// Of the data read, only what fits into the cache is kept.
+    fn read_up_to(&mut self, target: u64) -> Result<()> {
+        with_chunk(self.chunk_len(u64::MAX), |chunk| {
+            while self.read_bytes() < target && !self.window.at_eof() {
+                let len = min(target - self.read_bytes(), chunk.len() as u64) as usize;
+                self.read_inner(&mut chunk[..len])?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Returns how many bytes to read from `inner` at once, to read `len` bytes into the cache.
+    ///
+    /// That is at most 8 KiB, and not more than the store can hold.
+    fn chunk_len(&self, len: u64) -> usize {
+        let capacity = self.store().capacity().unwrap_or(u64::MAX).max(1);
+        min(min(len, capacity), CHUNK_SIZE as u64) as usize
+    }
+
+    /// Returns the next `len` bytes without moving the stream position.
+    ///
+    /// As much data as necessary is read ahead from `inner` and kept in the cache,
+    /// even if that is more than `2 * keep_size` bytes. The returned slice is
+    /// shorter than `len` only if the stream ends before, or at the end of the pinned head,
+    /// see [`SeekableReader::pin_head`]. Peeking beyond the pinned head, when the data after it
+    /// is no longer cached, recreates `inner`, and fails with a [`SeekOutOfWindow`] error if
+    /// `inner` cannot be recreated.
+    /// If the store cannot hold `len` bytes, like a small [`FixedStore`],
+    /// this fails with [`ErrorKind::InvalidInput`](io::ErrorKind::InvalidInput) before reading.
+    #[cfg_attr(feature = "std", doc = " ```")]
+    #[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+    /// use std::io::Read;
+    /// use seekable_reader::SeekableReader;
+    ///
+    /// let source = b"\x89PNG\r\n\x1a\n...";
+    /// let mut reader = SeekableReader::new(&source[..], 2);
+    /// assert!(reader.peek(4).unwrap().ends_with(b"PNG"));
+    /// let mut buffer = [0; 8];
+    /// reader.read_exact(&mut buffer).unwrap();
+    /// assert_eq!(&buffer, &source[..8]);
+    /// ```
+    pub fn peek(&mut self, len: usize) -> Result<&[u8]> {
+        if self
+            .store()
+            .capacity()
+            .is_some_and(|capacity| len as u64 > capacity)
+        {
+            return Err(error::peek_beyond_capacity());
         }
-        if self.current_buffer.len() == self.keep_size {
-            mem::swap(&mut self.older_buffer, &mut self.current_buffer);
-            self.current_buffer.clear();
+        if self.window.reaches_gap(len) {
+            self.reopen(self.get_stream_position())?;
         }
-        self.pos = Position::FrontBuffer(self.current_buffer.len());
-        Ok(read_bytes)
+        let target = self.get_stream_position().saturating_add(len as u64);
+        with_chunk(self.chunk_len(len as u64), |chunk| -> Result<()> {
+            while self.read_bytes() < target && !self.window.at_eof() {
+                let len = min(target - self.read_bytes(), chunk.len() as u64) as usize;
+                let read_bytes = self.inner.read(&mut chunk[..len])?;
+                self.window.append(&chunk[..read_bytes], len)?;
+            }
+            Ok(())
+        })?;
+        self.window.peek(len)
     }
 
-    pub fn get_stream_position(&self) -> usize {
-        match self.pos {
-            Position::FrontBuffer(pos) => self.buffer_begins_at_pos + self.older_buffer.len() + pos,
-            Position::BackBuffer(pos) => self.buffer_begins_at_pos + pos,
+    /// Like [`SeekableReader::peek`], but fails if fewer than `len` bytes are available,
+    /// like when the stream ends before, with
+    #[cfg_attr(
+        feature = "std",
+        doc = " [`ErrorKind::UnexpectedEof`](io::ErrorKind::UnexpectedEof)."
+    )]
+    #[cfg_attr(
+        not(feature = "std"),
+        doc = " [`ErrorKind::Other`](io::ErrorKind::Other)."
+    )]
+    pub fn peek_exact(&mut self, len: usize) -> Result<&[u8]> {
+        let data = self.peek(len)?;
+        if data.len() < len {
+            return Err(error::unexpected_eof(
+                "failed to peek the requested amount of data",
+            ));
         }
+        Ok(data)
+    }
+
+    /// Returns the total length of the stream, if it is known.
+    ///
+    /// It is known once `inner` reached EOF, or if a [`RangeSource`], a seekable `inner`,
+    /// or [`SeekableReader::set_len_hint`] told it.
+    pub fn stream_len(&self) -> Option<u64> {
+        self.window.stream_len()
     }
 
-    fn seek_backwards(&mut self, shift: usize) -> Result<u64> {
-        let mut shift = shift;
-        if let Position::FrontBuffer(pos) = self.pos {
-            if shift > pos {
-                self.pos = Position::BackBuffer(self.older_buffer.len() - 1);
-                shift -= pos + 1;
-            } else {
-                self.pos = Position::FrontBuffer(pos - shift);
+    /// Tells the length of the stream, when it is known from elsewhere, like from HTTP headers.
+    ///
+    /// Seeking relative to the end then does not read the stream, and seeking beyond the end
+    /// fails with [`ErrorKind::InvalidInput`](io::ErrorKind::InvalidInput).
+    /// If `inner` goes on after it, reading fails with
+    /// [`ErrorKind::InvalidData`](io::ErrorKind::InvalidData) instead of returning more data.
+    /// If `inner` ends before, the stream was truncated, and reading fails with
+    #[cfg_attr(
+        feature = "std",
+        doc = " [`ErrorKind::UnexpectedEof`](io::ErrorKind::UnexpectedEof)."
+    )]
+    #[cfg_attr(
+        not(feature = "std"),
+        doc = " [`ErrorKind::Other`](io::ErrorKind::Other)."
+    )]
+    pub fn set_len_hint(&mut self, len: u64) {
+        self.window.set_len_hint(len);
+    }
+
+    pub fn get_stream_position(&self) -> u64 {
+        self.window.pos()
+    }
+
+    /// Recreates or seeks `inner`, and reads it up to `target`.
+    fn reopen(&mut self, target: u64) -> Result<u64> {
+        match &mut self.reopen {
+            None => return Err(self.window.out_of_window_error(target)),
+            Some(Reopen::Restart(reopen)) => {
+                self.inner = reopen()?;
+                self.window.reset(0)?;
+            }
+            Some(Reopen::At(reopen)) => {
+                self.inner = reopen(target)?;
+                self.window.reset(target)?;
+            }
+            Some(Reopen::Seek(seek)) => {
+                seek(&mut self.inner, target)?;
+                self.window.reset(target)?;
             }
         }
+        self.reopen_count += 1;
+        self.seek_to(SeekFrom::Start(target))
+    }
 
-        if let Position::BackBuffer(pos) = self.pos {
-            let shift = min(shift, pos);
-            let newpos = self.buffer_begins_at_pos + pos - shift;
-            self.pos = Position::BackBuffer(newpos);
+    /// Makes the data at the stream position available, after reading beyond the pinned head.
+    ///
+    /// Without a way to recreate `inner`, this fails with a [`SeekOutOfWindow`] error.
+    fn leave_gap(&mut self) -> Result<()> {
+        if self.window.in_gap() {
+            self.reopen(self.get_stream_position())?;
         }
+        Ok(())
+    }
 
-        Ok(self.get_stream_position() as u64)
+    /// Returns whether a forward seek to `target` is better done by recreating `inner`.
+    ///
+    /// This is never the case while a [`Checkpoint`] is alive, since it would drop the pinned data.
+    fn is_far_seek(&self, target: u64) -> bool {
+        let far = max(2 * self.keep_size() as u64, FAR_SEEK);
+        matches!(self.reopen, Some(Reopen::At(_) | Reopen::Seek(_)))
+            && !self.window.is_pinned()
+            && target - self.read_bytes() > far
     }
 
-    fn seek_forwards(&mut self, shift: usize) -> Result<u64> {
-        let mut shift = shift;
-        if let Position::BackBuffer(pos) = self.pos {
-            let remaining_in_back_buffer = self.older_buffer.len() - pos;
-            if shift >= remaining_in_back_buffer {
-                self.pos = Position::FrontBuffer(0);
-                shift -= remaining_in_back_buffer;
-            } else {
-                self.pos = Position::BackBuffer(pos + shift);
-            }
+    // The I/O trait implementations, shared by those of std and embedded-io
+
+    fn read_into(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.leave_gap()?;
+        let from_cache = self.window.read_cached(buf)?;
+        let from_inner = &mut buf[from_cache..];
+        if from_inner.is_empty() || !self.window.at_end() {
+            Ok(from_cache)
+        } else if from_inner.len() >= self.min_read {
+            Ok(from_cache + self.read_inner(from_inner)?)
+        } else {
+            // Small reads go through the cache
+            self.read_ahead(self.min_read)?;
+            Ok(from_cache + self.window.read_cached(from_inner)?)
         }
+    }
 
-        if let Position::FrontBuffer(pos) = self.pos {
-            let remaining_in_front_buffer = self.current_buffer.len() - pos;
-            if shift > remaining_in_front_buffer {
+    fn fill(&mut self) -> Result<&[u8]> {
+        self.leave_gap()?;
+        if self.window.at_end() {
+            self.read_ahead(max(self.keep_size().clamp(1, CHUNK_SIZE), self.min_read))?;
+        }
+        self.window.cached()
+    }
+
+    /// Reads up to `len` bytes from `inner` into the cache, without moving the stream position.
+    fn read_ahead(&mut self, len: usize) -> Result<()> {
+        with_chunk(self.chunk_len(len as u64), |chunk| {
+            let read_bytes = self.inner.read(chunk)?;
+            self.window.append(&chunk[..read_bytes], chunk.len())
+        })
+    }
+
+    fn seek_to(&mut self, pos: SeekFrom) -> Result<u64> {
+        let old_position = self.get_stream_position();
+        match self.window.seek(pos)? {
+            window::Seek::Done(pos) => Ok(pos),
+            window::Seek::Forward(target) if self.is_far_seek(target) => self.reopen(target),
+            window::Seek::Forward(target) => {
                 // We have to read additional data the user is not (yet) interested in
-                shift -= remaining_in_front_buffer;
-                self.pos = Position::FrontBuffer(self.current_buffer.len());
-                let mut _discarded_data = vec![0; shift];
-                self.read_inner(&mut _discarded_data)?;
-            } else {
-                self.pos = Position::FrontBuffer(pos + shift);
+                self.read_up_to(target)?;
+                Ok(self.get_stream_position())
+            }
+            window::Seek::End(shift) => {
+                self.read_up_to(u64::MAX)?;
+                match self.window.seek_from_end(shift, old_position)? {
+                    window::Seek::Reopen(target) => self.reopen(target),
+                    window::Seek::Done(pos) => Ok(pos),
+                    _ => unreachable!("the stream length is known at EOF"),
+                }
             }
+            window::Seek::Reopen(target) => self.reopen(target),
         }
-
-        Ok(self.get_stream_position() as u64)
     }
 }
 
 /// A SeekableReader can be read just normally:
-///  ```
+#[cfg_attr(feature = "std", doc = " ```")]
+#[cfg_attr(not(feature = "std"), doc = " ```ignore")]
 /// use std::io::Read;
 /// use seekable_reader::SeekableReader;
 ///
@@ -176,62 +675,61 @@ impl<R: Read> SeekableReader<R> {
 /// let bytes: Vec<_> = reader.bytes().map(|b| b.unwrap()).collect();
 /// assert_eq!(&source, &bytes);
 /// ```
-impl<R: Read> Read for SeekableReader<R> {
+#[cfg(feature = "std")]
+impl<R: Read, S: CacheStore> Read for SeekableReader<R, S> {
     /// Read something from this source and write it into buffer, returning how many bytes were read.
     ///
-    /// `read` will never read more than `buf.len()` from the underlying reader. But it may have read less
-    /// than it returns, in case the user seeked backwards before, causing the cache to be used.
+    /// Data the user seeked back to is served from the cache. Otherwise, `read` reads from
+    /// the underlying reader, directly into `buf` if it is large enough. Smaller reads, like
+    /// below the builder's [`min_read`](SeekableReaderBuilder::min_read), read ahead into the cache,
+    /// so more than `buf.len()` bytes may be read from the underlying reader.
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        match self.pos {
-            Position::FrontBuffer(pos) => {
-                let cached = &self.current_buffer[pos..];
-                let (from_cache, from_inner) = buf.split_at_mut(min(cached.len(), buf.len()));
-                from_cache.copy_from_slice(&cached[..from_cache.len()]);
-                self.pos = Position::FrontBuffer(pos + from_cache.len());
-                if !from_inner.is_empty() {
-                    Ok(from_cache.len() + self.read_inner(from_inner)?)
-                } else {
-                    Ok(from_cache.len())
-                }
-            }
-            Position::BackBuffer(pos) => {
-                let cached = &self.older_buffer[pos..];
-                let cached = &cached[..min(cached.len(), buf.len())];
-                let (from_cache, other) = buf.split_at_mut(min(cached.len(), buf.len()));
-                from_cache.copy_from_slice(cached);
-                if !other.is_empty() {
-                    self.pos = Position::FrontBuffer(0);
-                    Ok(from_cache.len() + self.read(other)?)
-                } else {
-                    self.pos = Position::BackBuffer(pos + cached.len());
-                    Ok(from_cache.len())
-                }
-            }
-        }
+        self.read_into(buf)
     }
 }
 
-impl<R: Read> Seek for SeekableReader<R> {
+/// The cached data can be used directly, without copying it into another buffer first:
+#[cfg_attr(feature = "std", doc = " ```")]
+#[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+/// use std::io::{BufRead, Seek, SeekFrom};
+/// use seekable_reader::SeekableReader;
+///
+/// let source = b"first line\nsecond line\n";
+/// let mut reader = SeekableReader::new(&source[..], 16);
+/// let mut line = String::new();
+/// reader.read_line(&mut line).unwrap();
+/// reader.seek(SeekFrom::Start(0)).unwrap();
+/// let lines: Vec<_> = reader.lines().map(|l| l.unwrap()).collect();
+/// assert_eq!(lines, ["first line", "second line"]);
+/// ```
+#[cfg(feature = "std")]
+impl<R: Read, S: CacheStore> BufRead for SeekableReader<R, S> {
+    /// Returns the cached data at the stream position, reading more from `inner` if there is none.
+    ///
+    /// At most `keep_size` bytes are read from `inner` at once.
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        self.fill()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.window.consume(amt);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read, S: CacheStore> Seek for SeekableReader<R, S> {
     fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
-        let old_position = self.get_stream_position();
-        match pos {
-            SeekFrom::Start(pos) if pos > old_position as u64 => {
-                self.seek_forwards(pos as usize - old_position)
-            }
-            SeekFrom::Start(pos) => self.seek_backwards(old_position - pos as usize),
-            SeekFrom::End(shift) => {
-                self.seek(SeekFrom::Start((old_position as i64 + shift) as u64))
-            }
-            SeekFrom::Current(shift) if shift > 0 => self.seek_forwards(shift as usize),
-            SeekFrom::Current(shift) => self.seek_backwards((-shift) as usize),
-        }
+        self.seek_to(pos)
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
-    use crate::SeekableReader;
-    use std::io::{Read, Seek, SeekFrom};
+    use crate::{
+        ArrayStore, BlockStore, FileRangeSource, OutOfWindowPolicy, SeekOutOfWindow,
+        SeekableReader, VecStore,
+    };
+    use std::io::{BufRead, Read, Seek, SeekFrom};
 
     #[test]
     fn readthrough_1byte_reserve() {
@@ -262,9 +760,8 @@ mod tests {
         let source = vec![1, 2, 3, 4, 5];
         let mut reader = SeekableReader::new(source.as_slice(), 5);
         let mut dest = [0; 5];
-        reader.read(&mut dest).unwrap();
-        assert_eq!(reader.older_buffer.len(), 5);
-        assert_eq!(reader.current_buffer.len(), 0);
+        assert_eq!(reader.read(&mut dest).unwrap(), 5);
+        assert_eq!(reader.buffered_size(), 5);
     }
 
     #[test]
@@ -273,13 +770,13 @@ mod tests {
         let mut reader = SeekableReader::new(source.as_slice(), 1);
         let mut dest = vec![];
         let mut buffer = [0; 1];
-        reader.read(&mut buffer).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1);
         dest.push(buffer[0]);
-        reader.seek_backwards(1).unwrap();
-        reader.read(&mut buffer).unwrap();
+        reader.seek(SeekFrom::Current(-1)).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1);
         dest.push(buffer[0]);
-        reader.seek_forwards(1).unwrap();
-        reader.read(&mut buffer).unwrap();
+        reader.seek(SeekFrom::Current(1)).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1);
         dest.push(buffer[0]);
         assert_eq!(dest, [1, 1, 3]);
     }
@@ -290,18 +787,18 @@ mod tests {
         let mut reader = SeekableReader::new(source.as_slice(), 2);
         let mut dest = vec![];
         let mut buffer = [0; 1];
-        reader.read(&mut buffer).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1);
         dest.push(buffer[0]);
-        reader.read(&mut buffer).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1);
         dest.push(buffer[0]);
-        reader.seek_backwards(2).unwrap();
-        reader.read(&mut buffer).unwrap();
+        reader.seek(SeekFrom::Current(-2)).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1);
         dest.push(buffer[0]);
-        reader.seek_forwards(2).unwrap();
-        reader.read(&mut buffer).unwrap();
+        reader.seek(SeekFrom::Current(2)).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1);
         dest.push(buffer[0]);
-        reader.seek_backwards(1).unwrap();
-        reader.read(&mut buffer).unwrap();
+        reader.seek(SeekFrom::Current(-1)).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1);
         dest.push(buffer[0]);
         assert_eq!(dest, [1, 2, 1, 4, 4]);
     }
@@ -311,21 +808,529 @@ mod tests {
         let source: Vec<u8> = (0..1536).map(|n| (n % 256) as u8).collect();
         let mut reader = SeekableReader::new(source.as_slice(), 1024);
         let mut buffer = [0; 1536];
-        reader.read(&mut buffer).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1536);
         assert_eq!(source.len(), buffer.len());
         assert_eq!(source, buffer);
         reader.seek(SeekFrom::Start(0)).unwrap();
-        reader.read(&mut buffer).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1536);
         assert_eq!(source, buffer);
         reader.seek(SeekFrom::Current(-1024)).unwrap();
         reader.seek(SeekFrom::Current(-512)).unwrap();
-        reader.read(&mut buffer).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1536);
         assert_eq!(source, buffer);
         reader.seek(SeekFrom::End(-1536)).unwrap();
-        reader.read(&mut buffer).unwrap();
+        assert_eq!(reader.read(&mut buffer).unwrap(), 1536);
         assert_eq!(source, buffer);
     }
 
+    #[test]
+    fn seek_from_end() {
+        let source: Vec<u8> = (0..100).collect();
+        let mut reader = SeekableReader::new(source.as_slice(), 10);
+        let mut buffer = [0; 5];
+        assert_eq!(reader.seek(SeekFrom::End(-5)).unwrap(), 95);
+        assert_eq!(reader.stream_len(), Some(100));
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [95, 96, 97, 98, 99]);
+        assert_eq!(reader.seek(SeekFrom::End(-20)).unwrap(), 80);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [80, 81, 82, 83, 84]);
+        assert!(reader.seek(SeekFrom::End(-21)).is_err());
+        assert!(reader.seek(SeekFrom::End(-101)).is_err());
+    }
+
+    #[test]
+    fn seek_out_of_window() {
+        let source: Vec<u8> = (0..100).collect();
+        let mut reader = SeekableReader::new(source.as_slice(), 10);
+        let mut buffer = [0; 50];
+        reader.read_exact(&mut buffer).unwrap();
+        let err = reader.seek(SeekFrom::Start(5)).unwrap_err();
+        let err = err.get_ref().unwrap().downcast_ref::<SeekOutOfWindow>();
+        assert_eq!(
+            err,
+            Some(&SeekOutOfWindow {
+                requested: 5,
+                earliest_available: 30
+            })
+        );
+        assert_eq!(reader.get_stream_position(), 50);
+        assert!(reader.seek(SeekFrom::Current(-51)).is_err());
+        assert_eq!(reader.get_stream_position(), 50);
+
+        reader.set_out_of_window_policy(OutOfWindowPolicy::Clamp);
+        assert_eq!(reader.seek(SeekFrom::Start(5)).unwrap(), 30);
+        assert_eq!(reader.seek(SeekFrom::End(-200)).unwrap(), 80);
+    }
+
+    #[test]
+    fn buf_read() {
+        let source = b"a,bb,ccc,dddd";
+        let mut reader = SeekableReader::new(&source[..], 2);
+        let mut field = vec![];
+        reader.read_until(b',', &mut field).unwrap();
+        reader.read_until(b',', &mut field).unwrap();
+        assert_eq!(field, b"a,bb,");
+        assert_eq!(reader.get_stream_position(), 5);
+        reader.seek(SeekFrom::Current(-3)).unwrap();
+        assert!(reader.fill_buf().unwrap().starts_with(b"bb,"));
+        reader.consume(3);
+        let fields: Vec<_> = reader.split(b',').map(|f| f.unwrap()).collect();
+        assert_eq!(fields, [&b"ccc"[..], b"dddd"]);
+    }
+
+    #[test]
+    fn reopen_on_miss() {
+        let source: Vec<u8> = (0..100).collect();
+        let open = move || Ok(std::io::Cursor::new(source.clone()));
+        let mut reader = SeekableReader::with_reopen(open, 4).unwrap();
+        let mut buffer = [0; 3];
+        assert_eq!(reader.seek(SeekFrom::End(-3)).unwrap(), 97);
+        reader.seek(SeekFrom::Current(-5)).unwrap();
+        assert_eq!(reader.reopen_count(), 0);
+        reader.seek(SeekFrom::Start(10)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [10, 11, 12]);
+        reader.seek(SeekFrom::Start(2)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [2, 3, 4]);
+        assert_eq!(reader.reopen_count(), 2);
+        assert_eq!(reader.seek(SeekFrom::End(-1)).unwrap(), 99);
+    }
+
+    #[test]
+    fn reopen_from_end() {
+        let source: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let open = {
+            let source = source.clone();
+            move || Ok(std::io::Cursor::new(source.clone()))
+        };
+        let mut readers = vec![
+            SeekableReader::with_reopen(open.clone(), 4).unwrap(),
+            SeekableReader::builder()
+                .keep_size(4)
+                .reopen(open.clone())
+                .build(open().unwrap())
+                .unwrap(),
+        ];
+        let mut reader = SeekableReader::with_reopen(open, 4).unwrap();
+        reader.set_out_of_window_policy(OutOfWindowPolicy::Reopen);
+        readers.push(reader);
+        for mut reader in readers {
+            assert_eq!(reader.seek(SeekFrom::End(-500)).unwrap(), 500);
+            let mut buffer = [0; 3];
+            reader.read_exact(&mut buffer).unwrap();
+            assert_eq!(buffer, [244, 245, 246]);
+            assert_eq!(reader.reopen_count(), 1);
+        }
+        // Without a way to recreate `inner`, the seek fails
+        let mut reader = SeekableReader::new(source.as_slice(), 4);
+        reader.set_out_of_window_policy(OutOfWindowPolicy::Reopen);
+        assert!(reader.seek(SeekFrom::End(-500)).is_err());
+        assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), 998);
+    }
+
+    #[test]
+    fn range_source() {
+        let source: Vec<u8> = (0..200_000).map(|n| (n % 251) as u8).collect();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), &source).unwrap();
+        let source_len = source.len();
+        let mut reader =
+            SeekableReader::with_range_source(FileRangeSource::new(file.path()).unwrap(), 8)
+                .unwrap();
+        assert_eq!(reader.stream_len(), Some(source_len as u64));
+        let mut buffer = [0; 4];
+        reader.read_exact(&mut buffer).unwrap();
+        reader.seek(SeekFrom::Start(150_000)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, &source[150_000..150_004]);
+        assert_eq!(reader.reopen_count(), 1);
+        reader.seek(SeekFrom::Current(100)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, &source[150_104..150_108]);
+        assert_eq!(reader.reopen_count(), 1);
+        reader.seek(SeekFrom::Start(3)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, &source[3..7]);
+        assert_eq!(reader.reopen_count(), 2);
+        assert_eq!(
+            reader.seek(SeekFrom::End(-2)).unwrap(),
+            source_len as u64 - 2
+        );
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(&rest, &source[source_len - 2..]);
+
+        // Seeking beyond the end stops at the end, instead of taking the target as the length
+        reader.seek(SeekFrom::Start(1000)).unwrap();
+        assert_eq!(
+            reader.seek(SeekFrom::Start(1_000_000)).unwrap(),
+            source_len as u64
+        );
+        assert_eq!(reader.stream_len(), Some(source_len as u64));
+        reader.seek(SeekFrom::End(-2)).unwrap();
+        rest.clear();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(&rest, &source[source_len - 2..]);
+    }
+
+    #[test]
+    fn seekable_inner() {
+        let source: Vec<u8> = (0..200).collect();
+        let mut inner = std::io::Cursor::new(source);
+        inner.set_position(20);
+        let mut reader = SeekableReader::with_seekable_inner(inner, 4).unwrap();
+        assert_eq!(reader.get_stream_position(), 20);
+        assert_eq!(reader.stream_len(), Some(200));
+        let mut buffer = [0; 3];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [20, 21, 22]);
+        reader.seek(SeekFrom::Start(2)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [2, 3, 4]);
+        assert_eq!(reader.reopen_count(), 1);
+        assert_eq!(reader.seek(SeekFrom::End(-3)).unwrap(), 197);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [197, 198, 199]);
+        assert_eq!(reader.read_bytes(), 200);
+    }
+
+    #[test]
+    fn far_seek_beyond_end() {
+        let source: Vec<u8> = (0..200_000).map(|n| (n % 251) as u8).collect();
+        let inner = std::io::Cursor::new(source.clone());
+        let mut reader = SeekableReader::with_seekable_inner(inner, 8).unwrap();
+        assert_eq!(reader.seek(SeekFrom::Start(1_000_000)).unwrap(), 200_000);
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(reader.stream_len(), Some(200_000));
+        assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), 199_998);
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(&rest, &source[199_998..]);
+    }
+
+    /// Implements `Seek`, but fails like a pipe does
+    struct Pipe<'a>(&'a [u8]);
+
+    impl Read for Pipe<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Seek for Pipe<'_> {
+        fn seek(&mut self, _: SeekFrom) -> std::io::Result<u64> {
+            Err(std::io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    #[test]
+    fn unseekable_inner() {
+        let source: Vec<u8> = (0..100).collect();
+        let mut reader = SeekableReader::with_seekable_inner(Pipe(&source), 4).unwrap();
+        assert_eq!(reader.stream_len(), None);
+        let mut buffer = [0; 20];
+        reader.read_exact(&mut buffer).unwrap();
+        reader.seek(SeekFrom::Current(-8)).unwrap();
+        let err = reader.seek(SeekFrom::Start(0)).unwrap_err();
+        assert!(err.get_ref().unwrap().is::<SeekOutOfWindow>());
+        assert_eq!(reader.seek(SeekFrom::End(-1)).unwrap(), 99);
+    }
+
+    /// Hands out at most 3 bytes per read
+    struct ShortReads(u8);
+
+    impl Read for ShortReads {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(3);
+            for byte in &mut buf[..len] {
+                *byte = self.0;
+                self.0 = self.0.wrapping_add(1);
+            }
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn seek_far_forwards() {
+        let mut reader = SeekableReader::new(ShortReads(0), 4);
+        assert_eq!(reader.seek(SeekFrom::Start(1_000_000)).unwrap(), 1_000_000);
+        assert_eq!(reader.read_bytes(), 1_000_000);
+        assert!(reader.buffered_size() <= 8);
+        let mut buffer = [0; 2];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [(1_000_000 % 256) as u8, (1_000_001 % 256) as u8]);
+        reader.seek(SeekFrom::Current(-4)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [(999_998 % 256) as u8, (999_999 % 256) as u8]);
+    }
+
+    #[test]
+    fn peek() {
+        let source: Vec<u8> = (0..100).collect();
+        let mut reader = SeekableReader::new(ShortReads(0), 2);
+        reader.read_exact(&mut [0; 3]).unwrap();
+        assert_eq!(reader.peek(20).unwrap(), &source[3..23]);
+        assert_eq!(reader.get_stream_position(), 3);
+        let mut buffer = [0; 30];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer[..], &source[3..33]);
+        let mut reader = SeekableReader::new(&source[..], 2);
+        reader.seek(SeekFrom::Start(95)).unwrap();
+        assert_eq!(reader.peek(10).unwrap(), &source[95..]);
+        let err = reader.peek_exact(6).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(reader.peek_exact(5).unwrap(), &source[95..]);
+    }
+
+    #[test]
+    fn pinned_head() {
+        let source: Vec<u8> = (0..200).collect();
+        let mut reader = SeekableReader::new(source.as_slice(), 4);
+        reader.pin_head(10).unwrap();
+        reader.read_exact(&mut [0; 150]).unwrap();
+        let mut buffer = [0; 4];
+        reader.seek(SeekFrom::Start(2)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [2, 3, 4, 5]);
+        assert_eq!(reader.peek(4).unwrap(), &[6, 7, 8, 9]);
+        assert!(reader.peek(5).is_err());
+        reader.seek(SeekFrom::End(-1)).unwrap();
+        reader.seek(SeekFrom::Start(9)).unwrap();
+        assert!(reader.seek(SeekFrom::Start(10)).is_err());
+        reader.read_exact(&mut buffer[..1]).unwrap();
+        let err = reader.read(&mut buffer).unwrap_err();
+        assert!(err.get_ref().unwrap().is::<SeekOutOfWindow>());
+    }
+
+    #[test]
+    fn pinned_head_with_reopen() {
+        let source: Vec<u8> = (0..200).collect();
+        let open = move || Ok(std::io::Cursor::new(source.clone()));
+        let mut reader = SeekableReader::with_reopen(open, 4).unwrap();
+        reader.pin_head(10).unwrap();
+        reader.read_exact(&mut [0; 150]).unwrap();
+        reader.seek(SeekFrom::Start(8)).unwrap();
+        assert_eq!(reader.reopen_count(), 0);
+        let mut buffer = [0; 4];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [8, 9, 10, 11]);
+        assert_eq!(reader.reopen_count(), 1);
+        reader.seek(SeekFrom::Start(5)).unwrap();
+        assert_eq!(reader.peek(8).unwrap(), &[5, 6, 7, 8, 9, 10, 11, 12]);
+    }
+
+    #[test]
+    fn hot_blocks() {
+        let source: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        let store = BlockStore::new(100, 1000);
+        let mut reader = SeekableReader::with_store(source.as_slice(), 10, store);
+        let mut buffer = [0; 150];
+        reader.read_exact(&mut buffer).unwrap();
+        reader.seek(SeekFrom::Start(600)).unwrap();
+        reader.read_exact(&mut buffer[..50]).unwrap();
+        reader.seek(SeekFrom::Start(40)).unwrap();
+        reader.read_exact(&mut buffer[..50]).unwrap();
+        assert_eq!(&buffer[..50], &source[40..90]);
+        reader.seek(SeekFrom::Start(1200)).unwrap();
+        reader.read_exact(&mut buffer[..20]).unwrap();
+        // The blocks that were not used again are evicted first
+        assert!(reader.seek(SeekFrom::Start(300)).is_err());
+        reader.seek(SeekFrom::Start(610)).unwrap();
+        reader.read_exact(&mut buffer[..20]).unwrap();
+        assert_eq!(&buffer[..20], &source[610..630]);
+        reader.seek(SeekFrom::Start(10)).unwrap();
+        reader.read_exact(&mut buffer[..20]).unwrap();
+        assert_eq!(&buffer[..20], &source[10..30]);
+        // Reading on into an evicted block fails, instead of ending the stream
+        let err = reader.read_exact(&mut buffer).unwrap_err();
+        assert!(err.get_ref().unwrap().is::<SeekOutOfWindow>());
+    }
+
+    #[test]
+    fn read_more_than_cache() {
+        let source: Vec<u8> = (0..100).collect();
+        let mut reader = SeekableReader::new(source.as_slice(), 10);
+        let mut buffer = [0; 45];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(reader.buffered_size(), 20);
+        assert_eq!(reader.read_bytes(), 45);
+        assert_eq!(reader.window_range(), 25..45);
+        reader.seek(SeekFrom::Current(-15)).unwrap();
+        let mut buffer = [0; 20];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer[..], &source[30..50]);
+        assert_eq!(reader.get_stream_position(), 50);
+        assert_eq!(reader.window_range(), 30..50);
+    }
+
+    /// A seekable stream of 8 GiB, whose bytes are their position modulo 256
+    struct Huge(u64);
+
+    impl Read for Huge {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = (buf.len() as u64).min((8 << 30) - self.0) as usize;
+            for byte in &mut buf[..len] {
+                *byte = self.0 as u8;
+                self.0 += 1;
+            }
+            Ok(len)
+        }
+    }
+
+    impl Seek for Huge {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.0 = match pos {
+                SeekFrom::Start(pos) => pos,
+                SeekFrom::Current(shift) => self.0.checked_add_signed(shift).unwrap(),
+                SeekFrom::End(shift) => (8u64 << 30).checked_add_signed(shift).unwrap(),
+            };
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn positions_beyond_4gib() {
+        let mut reader = SeekableReader::with_seekable_inner(Huge(0), 4).unwrap();
+        let target = (5 << 30) + 7;
+        assert_eq!(reader.seek(SeekFrom::Start(target)).unwrap(), target);
+        let mut buffer = [0; 2];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [7, 8]);
+        assert_eq!(reader.get_stream_position(), target + 2);
+        assert_eq!(reader.window_range(), target..target + 2);
+        assert_eq!(reader.seek(SeekFrom::Current(-1)).unwrap(), target + 1);
+        assert_eq!(reader.seek(SeekFrom::End(-1)).unwrap(), (8 << 30) - 1);
+        reader.read_exact(&mut buffer[..1]).unwrap();
+        assert_eq!(buffer[0], 255);
+    }
+
+    #[test]
+    fn fixed_buffer_like_ring() {
+        let source: Vec<u8> = (0..=255).collect();
+        let mut ring = SeekableReader::new(source.as_slice(), 8);
+        let mut buffer = [0; 16];
+        let mut fixed = SeekableReader::with_buffer(source.as_slice(), &mut buffer);
+        let seeks = [
+            SeekFrom::Current(30),
+            SeekFrom::Current(-16),
+            SeekFrom::Current(-1),
+            SeekFrom::Start(100),
+            SeekFrom::Start(90),
+            SeekFrom::End(-20),
+            SeekFrom::End(-40),
+            SeekFrom::Current(-3),
+        ];
+        for pos in seeks {
+            let expected = ring.seek(pos).map_err(|err| err.kind());
+            assert_eq!(fixed.seek(pos).map_err(|err| err.kind()), expected);
+            let (mut from_ring, mut from_fixed) = ([0; 5], [0; 5]);
+            assert_eq!(
+                fixed.read(&mut from_fixed).unwrap(),
+                ring.read(&mut from_ring).unwrap()
+            );
+            assert_eq!(from_fixed, from_ring);
+            assert_eq!(fixed.window_range(), ring.window_range());
+        }
+    }
+
+    #[test]
+    fn len_hint() {
+        let source: Vec<u8> = (0..100).collect();
+        let mut reader = SeekableReader::with_len(source.as_slice(), 8, 100);
+        let err = reader.seek(SeekFrom::Start(101)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(reader.seek(SeekFrom::End(-4)).unwrap(), 96);
+        assert_eq!(reader.read_bytes(), 96);
+        let mut data = vec![];
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, [96, 97, 98, 99]);
+        assert_eq!(reader.seek(SeekFrom::End(0)).unwrap(), 100);
+
+        let mut reader = SeekableReader::with_len(source.as_slice(), 8, 200);
+        let err = reader.seek(SeekFrom::End(-50)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(reader.read_bytes(), 100);
+        let err = reader.fill_buf().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+        // The stream is longer than the hint
+        let mut reader = SeekableReader::with_len(source.as_slice(), 8, 50);
+        reader.read_exact(&mut [0; 40]).unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(reader.stream_len(), Some(50));
+        assert!(reader.read_bytes() <= 50);
+        assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), 48);
+    }
+
+    #[test]
+    fn pinned_tail() {
+        let source: Vec<u8> = (0..=255).collect();
+        let mut reader = SeekableReader::with_len(source.as_slice(), 4, 256);
+        reader.pin_tail(32).unwrap();
+        reader.read_exact(&mut [0; 100]).unwrap();
+        // With a known length, only the actual tail is kept beyond the window
+        assert_eq!(reader.buffered_size(), 8);
+        reader.seek(SeekFrom::End(-2)).unwrap();
+        for k in [32, 1, 17, 32] {
+            let mut byte = [0];
+            reader.seek(SeekFrom::End(-k)).unwrap();
+            reader.read_exact(&mut byte).unwrap();
+            assert_eq!(byte[0] as i64, 256 - k);
+        }
+        assert!(reader.seek(SeekFrom::End(-33)).is_err());
+
+        let mut reader = SeekableReader::new(source.as_slice(), 4);
+        reader.pin_tail(32).unwrap();
+        reader.read_exact(&mut [0; 100]).unwrap();
+        assert_eq!(reader.buffered_size(), 32);
+
+        let mut reader = SeekableReader::<_, ArrayStore<16>>::with_array(source.as_slice());
+        let err = reader.pin_tail(32).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        reader.pin_tail(8).unwrap();
+    }
+
+    #[test]
+    fn seek_back_in_vec_store() {
+        let source: Vec<u8> = (0..100).collect();
+        let mut reader = SeekableReader::with_store(source.as_slice(), 4, VecStore::new());
+        let mut buffer = [0; 30];
+        for _ in 0..3 {
+            reader.read_exact(&mut buffer).unwrap();
+        }
+        assert_eq!(reader.seek(SeekFrom::Start(2)).unwrap(), 2);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer[..], &source[2..32]);
+    }
+
+    #[cfg(feature = "tempfile")]
+    #[test]
+    fn seek_into_spill_file() {
+        let source: Vec<u8> = (0..=255).collect();
+        let mut reader = SeekableReader::with_spill(source.as_slice(), 16).unwrap();
+        let mut buffer = [0; 7];
+        for _ in 0..30 {
+            reader.read_exact(&mut buffer).unwrap();
+        }
+        assert_eq!(reader.seek(SeekFrom::Start(3)).unwrap(), 3);
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [3, 4, 5, 6, 7, 8, 9]);
+        reader.seek(SeekFrom::Current(150)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [160, 161, 162, 163, 164, 165, 166]);
+        let mut rest = vec![];
+        reader.seek(SeekFrom::Start(100)).unwrap();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(&rest[..], &source[100..]);
+        reader.seek(SeekFrom::End(-256)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [0, 1, 2, 3, 4, 5, 6]);
+    }
+
     #[test]
     fn small_result_test() {
         let source: Vec<u8> = (0..1536).map(|n| (n % 256) as u8).collect();
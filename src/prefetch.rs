@@ -0,0 +1,254 @@
+//! Reading ahead on a background thread, see [`Prefetch`].
+use crate::{SeekableReader, CHUNK_SIZE};
+use core::cmp::min;
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Read, Result};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+/// A reader that reads ahead of the consumer on a background thread
+///
+/// A worker thread reads from the inner reader until `read_ahead` bytes are waiting
+/// for the consumer, and continues when the consumer has taken some of them.
+/// So a slow inner reader, like a pipe or a decoder, can make progress while the
+/// consumer is busy. See [`SeekableReader::with_prefetch`].
+///
+/// An error of the inner reader is returned once the data read before it was consumed.
+/// After that, and after the worker thread panicked, reading fails.
+/// When the Prefetch is dropped, the worker thread stops after its current read.
+pub struct Prefetch {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+struct State {
+    buffer: VecDeque<u8>,
+    read_ahead: usize,
+    /// The error the worker thread stopped with
+    error: Option<Error>,
+    /// Whether the worker thread reached EOF
+    eof: bool,
+    /// Whether the worker thread stopped
+    done: bool,
+    /// Whether the consumer is gone
+    closed: bool,
+}
+
+impl Prefetch {
+    /// Starts a worker thread which reads ahead up to `read_ahead` bytes from `inner`.
+    pub fn new<R: Read + Send + 'static>(inner: R, read_ahead: usize) -> Result<Prefetch> {
+        let read_ahead = read_ahead.max(1);
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                buffer: VecDeque::with_capacity(read_ahead),
+                read_ahead,
+                error: None,
+                eof: false,
+                done: false,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        });
+        let worker = Arc::clone(&shared);
+        thread::Builder::new()
+            .name("seekable_reader-prefetch".into())
+            .spawn(move || read_ahead_of(inner, &worker))?;
+        Ok(Prefetch { shared })
+    }
+
+    /// Returns how many bytes were read ahead and wait for the consumer.
+    pub fn buffered(&self) -> usize {
+        self.shared.lock().buffer.len()
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // The state stays consistent even if a thread panicked while holding the lock
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn wait<'a>(&self, state: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        self.changed
+            .wait(state)
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Marks the worker thread as stopped when it returns or panics
+struct Done<'a>(&'a Shared);
+
+impl Drop for Done<'_> {
+    fn drop(&mut self) {
+        self.0.lock().done = true;
+        self.0.changed.notify_all();
+    }
+}
+
+/// The worker thread
+fn read_ahead_of<R: Read>(mut inner: R, shared: &Shared) {
+    let _done = Done(shared);
+    let mut chunk = [0; CHUNK_SIZE];
+    loop {
+        let mut state = shared.lock();
+        while state.buffer.len() >= state.read_ahead && !state.closed {
+            state = shared.wait(state);
+        }
+        if state.closed {
+            return;
+        }
+        let room = state.read_ahead - state.buffer.len();
+        drop(state);
+        let chunk = &mut chunk[..min(room, CHUNK_SIZE)];
+        match inner.read(chunk) {
+            Ok(0) => {
+                shared.lock().eof = true;
+                return;
+            }
+            Ok(read_bytes) => {
+                shared.lock().buffer.extend(&chunk[..read_bytes]);
+                shared.changed.notify_all();
+            }
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => {
+                shared.lock().error = Some(err);
+                return;
+            }
+        }
+    }
+}
+
+impl Read for Prefetch {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut state = self.shared.lock();
+        while state.buffer.is_empty() && !state.done {
+            state = self.shared.wait(state);
+        }
+        if !state.buffer.is_empty() {
+            let len = min(state.buffer.len(), buf.len());
+            for (byte, data) in buf.iter_mut().zip(state.buffer.drain(..len)) {
+                *byte = data;
+            }
+            self.shared.changed.notify_all();
+            return Ok(len);
+        }
+        if state.eof {
+            return Ok(0);
+        }
+        Err(state
+            .error
+            .take()
+            .unwrap_or_else(|| Error::other("the read-ahead thread stopped")))
+    }
+}
+
+impl Drop for Prefetch {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+impl SeekableReader<Prefetch> {
+    /// Create a new SeekableReader which reads ahead of the stream position on a background thread.
+    ///
+    /// Besides the data kept for seeking backwards, up to `read_ahead` bytes are read
+    /// from `inner` in advance, see [`Prefetch`].
+    /// Everything else works like with [`SeekableReader::new`].
+    ///  ```
+    /// use std::io::{Read, Seek, SeekFrom};
+    /// use seekable_reader::SeekableReader;
+    ///
+    /// let source: Vec<u8> = (0..100).collect();
+    /// let mut reader = SeekableReader::with_prefetch(std::io::Cursor::new(source), 10, 32).unwrap();
+    /// reader.read_exact(&mut [0; 50]).unwrap();
+    /// reader.seek(SeekFrom::Current(-5)).unwrap();
+    /// let mut buffer = [0; 3];
+    /// reader.read_exact(&mut buffer).unwrap();
+    /// assert_eq!(buffer, [45, 46, 47]);
+    /// ```
+    pub fn with_prefetch<R: Read + Send + 'static>(
+        inner: R,
+        keep_size: usize,
+        read_ahead: usize,
+    ) -> Result<SeekableReader<Prefetch>> {
+        Ok(SeekableReader::new(
+            Prefetch::new(inner, read_ahead)?,
+            keep_size,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Prefetch;
+    use std::io::{Error, ErrorKind, Read};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// An endless stream, which counts how much was read from it
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Read for Counted {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let pos = self.0.fetch_add(buf.len(), Ordering::SeqCst);
+            for (n, byte) in buf.iter_mut().enumerate() {
+                *byte = (pos + n) as u8;
+            }
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn backpressure() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let mut prefetch = Prefetch::new(Counted(Arc::clone(&count)), 100).unwrap();
+        let mut buffer = [0; 30];
+        prefetch.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer[29], 29);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(count.load(Ordering::SeqCst) <= 130);
+        let mut buffer = vec![0; 10_000];
+        prefetch.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer[9_999], (10_029 % 256) as u8);
+    }
+
+    #[test]
+    fn error_after_data() {
+        let failing = (&[1, 2, 3][..]).chain(FailingRead);
+        let mut prefetch = Prefetch::new(failing, 100).unwrap();
+        let mut buffer = [0; 3];
+        prefetch.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, [1, 2, 3]);
+        let err = prefetch.read(&mut buffer).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        assert!(prefetch.read(&mut buffer).is_err());
+    }
+
+    struct FailingRead;
+
+    impl Read for FailingRead {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(Error::from(ErrorKind::BrokenPipe))
+        }
+    }
+
+    #[test]
+    fn eof() {
+        let source: Vec<u8> = (0..100).collect();
+        let mut prefetch = Prefetch::new(std::io::Cursor::new(source.clone()), 7).unwrap();
+        let mut data = vec![];
+        prefetch.read_to_end(&mut data).unwrap();
+        assert_eq!(data, source);
+        assert_eq!(prefetch.read(&mut [0; 4]).unwrap(), 0);
+    }
+}
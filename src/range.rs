@@ -0,0 +1,60 @@
+use crate::io::{Read, Result};
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::{Seek, SeekFrom};
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+/// A source which can open a reader at any stream position
+///
+/// This fits sources that can't seek, but are cheap to restart at another position,
+/// like HTTP servers and object stores supporting range requests.
+/// See [`SeekableReader::with_range_source`](crate::SeekableReader::with_range_source).
+#[allow(clippy::len_without_is_empty)]
+pub trait RangeSource {
+    type Reader: Read;
+
+    /// Opens a reader for the data beginning at the stream position `offset`.
+    fn open_at(&mut self, offset: u64) -> Result<Self::Reader>;
+
+    /// Returns the total length of the stream, if known.
+    fn len(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// A [`RangeSource`] reading a local file
+///
+/// Useful to test range-based readers without a server.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct FileRangeSource {
+    path: PathBuf,
+    len: u64,
+}
+
+#[cfg(feature = "std")]
+impl FileRangeSource {
+    /// Creates a source for the file at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Result<FileRangeSource> {
+        let path = path.into();
+        let len = path.metadata()?.len();
+        Ok(FileRangeSource { path, len })
+    }
+}
+
+#[cfg(feature = "std")]
+impl RangeSource for FileRangeSource {
+    type Reader = File;
+
+    fn open_at(&mut self, offset: u64) -> Result<File> {
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(file)
+    }
+
+    fn len(&self) -> Option<u64> {
+        Some(self.len)
+    }
+}
@@ -0,0 +1,315 @@
+//! Several readers over one stream, see [`SharedSeekableSource`].
+use crate::error::negative_seek;
+use crate::{CacheStore, RingStore, SeekableReader};
+use std::collections::HashMap;
+use std::io::{Read, Result, Seek, SeekFrom};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A stream that is read by several cursors, each at its own pace
+///
+/// The source owns the inner reader and the cache, and hands out [`SharedCursor`]s.
+/// Like with a [`SeekableReader`], each cursor can seek back at least `keep_size` bytes.
+/// Data is kept from there for the slowest cursor on, but at most the last `limit` bytes.
+/// As long as there are no cursors, the last `limit` bytes are kept.
+/// When the fastest cursor is further ahead, the slowest one loses its data,
+/// and reading from it fails with a [`SeekOutOfWindow`](crate::SeekOutOfWindow) error.
+///  ```
+/// use std::io::Read;
+/// use seekable_reader::SharedSeekableSource;
+///
+/// let source: Vec<u8> = (0..100).collect();
+/// let shared = SharedSeekableSource::new(source.as_slice(), 4, 64);
+/// let mut fast = shared.cursor();
+/// let mut slow = shared.cursor();
+/// let mut buffer = [0; 50];
+/// fast.read_exact(&mut buffer).unwrap();
+/// slow.read_exact(&mut buffer[..10]).unwrap();
+/// assert_eq!(&buffer[..10], &source[..10]);
+/// ```
+pub struct SharedSeekableSource<R: Read, S: CacheStore = RingStore> {
+    shared: Arc<Mutex<Shared<R, S>>>,
+}
+
+/// A reader over a [`SharedSeekableSource`], with its own stream position
+///
+/// Cursors can be sent to other threads. Cloning a cursor creates another one at the same position.
+pub struct SharedCursor<R: Read, S: CacheStore = RingStore> {
+    shared: Arc<Mutex<Shared<R, S>>>,
+    id: usize,
+}
+
+struct Shared<R: Read, S: CacheStore> {
+    reader: SeekableReader<R, S>,
+    keep_size: u64,
+    limit: u64,
+    /// Stream positions of the cursors
+    cursors: HashMap<usize, u64>,
+    next_id: usize,
+}
+
+impl<R: Read> SharedSeekableSource<R> {
+    /// Creates a source reading from `inner`, which keeps at most `limit` bytes.
+    pub fn new(inner: R, keep_size: usize, limit: usize) -> SharedSeekableSource<R> {
+        let store = RingStore::with_capacity(limit);
+        SharedSeekableSource::with_store(inner, keep_size, limit, store)
+    }
+}
+
+impl<R: Read, S: CacheStore> SharedSeekableSource<R, S> {
+    /// Creates a source which keeps its cache in `store`.
+    ///
+    /// The source tells the store to drop everything that no cursor needs,
+    /// and everything older than the last `limit` bytes.
+    pub fn with_store(
+        inner: R,
+        keep_size: usize,
+        limit: usize,
+        store: S,
+    ) -> SharedSeekableSource<R, S> {
+        let mut shared = Shared {
+            reader: SeekableReader::with_store(inner, keep_size, store),
+            keep_size: keep_size as u64,
+            limit: limit as u64,
+            cursors: HashMap::new(),
+            next_id: 0,
+        };
+        shared.retain();
+        SharedSeekableSource {
+            shared: Arc::new(Mutex::new(shared)),
+        }
+    }
+
+    /// Reads data at the stream position `offset` into `buf`, without a cursor.
+    ///
+    /// The data is read from the cache if possible, and from `inner` otherwise.
+    /// Like with a cursor, `offset` must not lie before the data that is still cached.
+    /// Like [`Read::read`], this may read less than `buf.len()` bytes, and it reads 0 bytes at the end of the stream.
+    ///  ```
+    /// use seekable_reader::SharedSeekableSource;
+    ///
+    /// let source: Vec<u8> = (0..100).collect();
+    /// let shared = SharedSeekableSource::new(source.as_slice(), 4, 64);
+    /// let mut buffer = [0; 3];
+    /// assert_eq!(shared.read_at(&mut buffer, 50).unwrap(), 3);
+    /// assert_eq!(buffer, [50, 51, 52]);
+    /// assert_eq!(shared.read_at(&mut buffer, 40).unwrap(), 3);
+    /// assert_eq!(buffer, [40, 41, 42]);
+    /// ```
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let mut shared = lock(&self.shared);
+        shared.reader.seek(SeekFrom::Start(offset))?;
+        shared.reader.read(buf)
+    }
+
+    /// Creates a cursor at the earliest stream position that is still cached.
+    pub fn cursor(&self) -> SharedCursor<R, S> {
+        let mut shared = lock(&self.shared);
+        let position = shared.reader.store().start();
+        shared.add_cursor(&self.shared, position)
+    }
+}
+
+impl<R: Read, S: CacheStore> Shared<R, S> {
+    fn add_cursor(
+        &mut self,
+        shared: &Arc<Mutex<Shared<R, S>>>,
+        position: u64,
+    ) -> SharedCursor<R, S> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.cursors.insert(id, position);
+        self.retain();
+        SharedCursor {
+            shared: Arc::clone(shared),
+            id,
+        }
+    }
+
+    /// Runs `op` on the reader at the stream position `position`,
+    /// and moves the cursor `id` to where `op` left the reader.
+    fn with_cursor<T, F>(&mut self, id: usize, position: u64, op: F) -> Result<T>
+    where
+        F: FnOnce(&mut SeekableReader<R, S>) -> Result<T>,
+    {
+        let result = self
+            .reader
+            .seek(SeekFrom::Start(position))
+            .and_then(|_| op(&mut self.reader));
+        if result.is_ok() {
+            self.cursors.insert(id, self.reader.get_stream_position());
+        }
+        self.retain();
+        result
+    }
+
+    /// Tells the window which data the cursors need, as far as it is within the last `limit` bytes.
+    fn retain(&mut self) {
+        let needed = match self.cursors.values().min() {
+            Some(slowest) => slowest.saturating_sub(self.keep_size),
+            None => 0,
+        };
+        self.reader.window.retain(needed, self.limit);
+    }
+}
+
+fn lock<R: Read, S: CacheStore>(shared: &Mutex<Shared<R, S>>) -> MutexGuard<'_, Shared<R, S>> {
+    // The state stays consistent even if reading from `inner` panicked
+    shared
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(feature = "positioned-io")]
+impl<R: Read, S: CacheStore> positioned_io::ReadAt for SharedSeekableSource<R, S> {
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> Result<usize> {
+        SharedSeekableSource::read_at(self, buf, pos)
+    }
+}
+
+impl<R: Read, S: CacheStore> SharedCursor<R, S> {
+    /// Returns the stream position of this cursor.
+    pub fn position(&self) -> u64 {
+        lock(&self.shared).cursors[&self.id]
+    }
+}
+
+impl<R: Read, S: CacheStore> Clone for SharedCursor<R, S> {
+    fn clone(&self) -> SharedCursor<R, S> {
+        let mut shared = lock(&self.shared);
+        let position = shared.cursors[&self.id];
+        shared.add_cursor(&self.shared, position)
+    }
+}
+
+impl<R: Read, S: CacheStore> Drop for SharedCursor<R, S> {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        shared.cursors.remove(&self.id);
+        shared.retain();
+    }
+}
+
+impl<R: Read, S: CacheStore> Read for SharedCursor<R, S> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut shared = lock(&self.shared);
+        let position = shared.cursors[&self.id];
+        shared.with_cursor(self.id, position, |reader| reader.read(buf))
+    }
+}
+
+impl<R: Read, S: CacheStore> Seek for SharedCursor<R, S> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let mut shared = lock(&self.shared);
+        let position = shared.cursors[&self.id];
+        let pos = match pos {
+            SeekFrom::Current(shift) => SeekFrom::Start(
+                position
+                    .checked_add_signed(shift)
+                    .ok_or_else(negative_seek)?,
+            ),
+            pos => pos,
+        };
+        // The data at the old position is not needed for this, it may be evicted already
+        let start = shared.reader.get_stream_position();
+        shared.with_cursor(self.id, start, |reader| reader.seek(pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{SeekOutOfWindow, SharedSeekableSource};
+    use std::io::{Read, Seek, SeekFrom};
+
+    #[test]
+    fn slow_cursor() {
+        let source: Vec<u8> = (0..200).collect();
+        let shared = SharedSeekableSource::new(source.as_slice(), 4, 50);
+        let mut fast = shared.cursor();
+        let mut slow = shared.cursor();
+        let mut buffer = [0; 10];
+        fast.read_exact(&mut buffer).unwrap();
+        slow.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer[0], 0);
+        fast.seek(SeekFrom::Current(30)).unwrap();
+        fast.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer[0], 40);
+        // The data before the slow cursor is kept, as long as it is within the limit
+        assert!(fast.seek(SeekFrom::Start(5)).is_err());
+        fast.seek(SeekFrom::Start(6)).unwrap();
+        fast.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer[0], 6);
+        slow.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer[0], 10);
+        fast.seek(SeekFrom::Start(100)).unwrap();
+        assert_eq!(fast.position(), 100);
+        let err = slow.read(&mut buffer).unwrap_err();
+        assert!(err.get_ref().unwrap().is::<SeekOutOfWindow>());
+        assert_eq!(slow.position(), 20);
+        slow.seek(SeekFrom::Start(90)).unwrap();
+        slow.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer[0], 90);
+    }
+
+    #[test]
+    fn dropped_cursor() {
+        let source: Vec<u8> = (0..200).collect();
+        let shared = SharedSeekableSource::new(source.as_slice(), 4, 100);
+        let mut cursor = shared.cursor();
+        let idle = cursor.clone();
+        cursor.read_exact(&mut [0; 60]).unwrap();
+        assert_eq!(idle.position(), 0);
+        drop(idle);
+        cursor.read_exact(&mut [0; 10]).unwrap();
+        assert_eq!(shared.cursor().position(), 56);
+        let mut buffer = [0; 1];
+        cursor.seek(SeekFrom::Current(-4)).unwrap();
+        cursor.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer[0], 66);
+    }
+
+    #[test]
+    fn read_at() {
+        let source: Vec<u8> = (0..200).collect();
+        let shared = SharedSeekableSource::new(source.as_slice(), 4, 50);
+        let mut cursor = shared.cursor();
+        let mut buffer = [0; 10];
+        assert_eq!(shared.read_at(&mut buffer, 30).unwrap(), 10);
+        assert_eq!(buffer[0], 30);
+        // The cursor is not moved, and its data is kept
+        cursor.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer[0], 0);
+        assert_eq!(shared.read_at(&mut buffer[..5], 5).unwrap(), 5);
+        assert_eq!(&buffer[..5], &source[5..10]);
+        assert_eq!(shared.read_at(&mut buffer, 195).unwrap(), 5);
+        assert_eq!(shared.read_at(&mut buffer, 300).unwrap(), 0);
+        assert!(shared.read_at(&mut buffer, 20).is_err());
+    }
+
+    #[cfg(feature = "positioned-io")]
+    #[test]
+    fn positioned_io() {
+        use positioned_io::ReadAt;
+        let source: Vec<u8> = (0..200).collect();
+        let shared = SharedSeekableSource::new(source.as_slice(), 4, 50);
+        let mut buffer = [0; 10];
+        ReadAt::read_exact_at(&shared, 100, &mut buffer).unwrap();
+        assert_eq!(&buffer, &source[100..110]);
+    }
+
+    #[test]
+    fn threads() {
+        let source: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+        let shared = SharedSeekableSource::new(source.as_slice(), 16, 1_000_000);
+        let cursors: Vec<_> = (0..4).map(|_| shared.cursor()).collect();
+        std::thread::scope(|scope| {
+            for mut cursor in cursors {
+                let source = &source;
+                scope.spawn(move || {
+                    let mut data = vec![];
+                    cursor.read_to_end(&mut data).unwrap();
+                    assert_eq!(&data, source);
+                });
+            }
+        });
+    }
+}
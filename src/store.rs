@@ -0,0 +1,983 @@
+//! Storage backends for the data a [`SeekableReader`](crate::SeekableReader) keeps around.
+//!
+//! A store holds a contiguous part of the stream, addressed by absolute stream positions.
+//! Data read from the inner reader is appended at the end, and the reader tells the store
+//! when older data is no longer needed. What the store does with that data is up to it:
+//! the [`RingStore`] discards it, while the other stores keep it around for later seeks.
+use crate::io::Result;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+use core::cmp::min;
+#[cfg(feature = "tempfile")]
+use std::fs::File;
+#[cfg(feature = "tempfile")]
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// Storage for the cached data of a [`SeekableReader`](crate::SeekableReader)
+pub trait CacheStore {
+    /// Appends data that was just read from the inner reader.
+    fn append(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Copies the data beginning at the stream position `offset` into `buf`.
+    ///
+    /// Returns how many bytes were copied, which is 0 if `offset` is not held by the store.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize>;
+
+    /// Returns contiguous data beginning at the stream position `offset`.
+    ///
+    /// The returned slice may be shorter than the data held after `offset`,
+    /// and it is empty if `offset` is not held by the store.
+    fn chunk_at(&mut self, offset: u64) -> Result<&[u8]>;
+
+    /// Returns up to `len` bytes beginning at the stream position `offset` as one slice.
+    ///
+    /// Unlike [`CacheStore::chunk_at`], the slice is only shorter than `len`
+    /// if the store holds less data after `offset`.
+    fn slice_at(&mut self, offset: u64, len: usize) -> Result<&[u8]>;
+
+    /// Tells the store that the data before the stream position `offset` is no longer needed.
+    ///
+    /// The store may discard it, or keep it to allow seeking there later.
+    fn evict_before(&mut self, offset: u64) -> Result<()>;
+
+    /// Appends `data`, and then tells the store that the data before the stream position
+    /// `offset` is no longer needed.
+    ///
+    /// Stores which discard data may skip the part of `data` they would discard right away.
+    fn append_and_evict(&mut self, data: &[u8], offset: u64) -> Result<()> {
+        self.append(data)?;
+        self.evict_before(offset)
+    }
+
+    /// Discards all data. The next appended data begins at the stream position `offset`.
+    fn reset(&mut self, offset: u64) -> Result<()>;
+
+    /// Returns the number of bytes held by the store.
+    fn len(&self) -> u64;
+
+    /// Returns the stream position of the first byte held by the store.
+    fn start(&self) -> u64;
+
+    /// Returns the stream position right after the last byte held by the store.
+    fn end(&self) -> u64 {
+        self.start() + self.len()
+    }
+
+    /// Returns whether the store holds no data at all.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns how many bytes the store can hold at most, if it is limited.
+    fn capacity(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns where the data held from the stream position `offset` on ends, but no further
+    /// than `limit`, or `None` if the data at `offset` is not held.
+    ///
+    /// Stores which hold a single range don't need to override this.
+    fn held_until(&self, offset: u64, limit: u64) -> Option<u64> {
+        (self.start()..self.end())
+            .contains(&offset)
+            .then(|| min(self.end(), limit))
+    }
+}
+
+/// Copies as much as possible of `parts`, skipping the first `skip` bytes, into `buf`
+fn copy_from_parts(parts: [&[u8]; 2], skip: usize, buf: &mut [u8]) -> usize {
+    let mut skip = skip;
+    let mut copied = 0;
+    for part in parts {
+        if skip >= part.len() {
+            skip -= part.len();
+            continue;
+        }
+        let part = &part[skip..];
+        skip = 0;
+        let len = min(part.len(), buf.len() - copied);
+        buf[copied..copied + len].copy_from_slice(&part[..len]);
+        copied += len;
+    }
+    copied
+}
+
+/// In-memory store which discards everything that is no longer needed
+///
+/// This is the default store, keeping memory usage bounded by the reader's window.
+#[derive(Debug, Default)]
+pub struct RingStore {
+    data: VecDeque<u8>,
+    start: u64,
+}
+
+impl RingStore {
+    /// Creates an empty store.
+    pub fn new() -> RingStore {
+        RingStore::default()
+    }
+
+    /// Creates an empty store with room for `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> RingStore {
+        RingStore {
+            data: VecDeque::with_capacity(capacity),
+            start: 0,
+        }
+    }
+}
+
+impl CacheStore for RingStore {
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        self.data.extend(data);
+        Ok(())
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        if offset < self.start || offset >= self.end() {
+            return Ok(0);
+        }
+        let (front, back) = self.data.as_slices();
+        Ok(copy_from_parts(
+            [front, back],
+            (offset - self.start) as usize,
+            buf,
+        ))
+    }
+
+    fn chunk_at(&mut self, offset: u64) -> Result<&[u8]> {
+        if offset < self.start || offset >= self.end() {
+            return Ok(&[]);
+        }
+        let skip = (offset - self.start) as usize;
+        let (front, back) = self.data.as_slices();
+        if skip < front.len() {
+            Ok(&front[skip..])
+        } else {
+            Ok(&back[skip - front.len()..])
+        }
+    }
+
+    fn slice_at(&mut self, offset: u64, len: usize) -> Result<&[u8]> {
+        if offset < self.start || offset >= self.end() {
+            return Ok(&[]);
+        }
+        let skip = (offset - self.start) as usize;
+        let len = min(len, self.data.len() - skip);
+        if skip + len > self.data.as_slices().0.len() {
+            self.data.make_contiguous();
+        }
+        Ok(&self.data.as_slices().0[skip..skip + len])
+    }
+
+    fn append_and_evict(&mut self, data: &[u8], offset: u64) -> Result<()> {
+        let skip = min(offset.saturating_sub(self.end()), data.len() as u64);
+        if skip > 0 {
+            self.reset(self.end() + skip)?;
+        }
+        self.append(&data[skip as usize..])?;
+        self.evict_before(offset)
+    }
+
+    fn evict_before(&mut self, offset: u64) -> Result<()> {
+        let evicted = min(offset.saturating_sub(self.start), self.len());
+        self.data.drain(..evicted as usize);
+        self.start += evicted;
+        Ok(())
+    }
+
+    fn reset(&mut self, offset: u64) -> Result<()> {
+        self.data.clear();
+        self.start = offset;
+        Ok(())
+    }
+
+    fn len(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn start(&self) -> u64 {
+        self.start
+    }
+}
+
+/// In-memory store in a buffer of fixed size, which never allocates
+///
+/// Like the [`RingStore`], it discards everything that is no longer needed. The buffer is
+/// either borrowed, see [`SliceStore`], or held inline, see [`ArrayStore`]. Data that does not
+/// fit into the buffer is discarded as well, oldest first, even if it is still needed:
+/// peeking further ahead, or keeping data after a [`Checkpoint`](crate::Checkpoint) or in a
+/// pinned head beyond the size of the buffer does not work.
+/// A buffer of `2 * keep_size` bytes is enough for everything else.
+///
+/// Reading, seeking, peeking and checkpoints never allocate with this store. Only a few
+/// things do: [`SeekableReader::pin_head`](crate::SeekableReader::pin_head) allocates the
+/// copy of the head once, a way to recreate `inner` is boxed, and with the `std` feature,
+/// errors with a message or a [`SeekOutOfWindow`](crate::SeekOutOfWindow) are boxed too.
+#[cfg_attr(feature = "std", doc = " ```")]
+#[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+/// use std::io::{Read, Seek, SeekFrom};
+/// use seekable_reader::{ArrayStore, SeekableReader};
+///
+/// let source: Vec<u8> = (0..100).collect();
+/// let mut reader = SeekableReader::with_store(source.as_slice(), 8, ArrayStore::<16>::new());
+/// reader.read_exact(&mut [0; 50]).unwrap();
+/// reader.seek(SeekFrom::Current(-8)).unwrap();
+/// let mut buffer = [0; 3];
+/// reader.read_exact(&mut buffer).unwrap();
+/// assert_eq!(buffer, [42, 43, 44]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FixedStore<B> {
+    buffer: B,
+    /// Index of the first byte held in the buffer
+    head: usize,
+    len: usize,
+    start: u64,
+}
+
+/// A [`FixedStore`] in a buffer supplied by the caller
+pub type SliceStore<'a> = FixedStore<&'a mut [u8]>;
+
+/// A [`FixedStore`] in an array of `N` bytes
+pub type ArrayStore<const N: usize> = FixedStore<[u8; N]>;
+
+impl<'a> FixedStore<&'a mut [u8]> {
+    /// Creates an empty store, which keeps its data in `buffer`.
+    pub const fn new(buffer: &'a mut [u8]) -> SliceStore<'a> {
+        FixedStore {
+            buffer,
+            head: 0,
+            len: 0,
+            start: 0,
+        }
+    }
+}
+
+impl<const N: usize> FixedStore<[u8; N]> {
+    /// Creates an empty store.
+    pub const fn new() -> ArrayStore<N> {
+        FixedStore {
+            buffer: [0; N],
+            head: 0,
+            len: 0,
+            start: 0,
+        }
+    }
+}
+
+impl<const N: usize> Default for FixedStore<[u8; N]> {
+    fn default() -> ArrayStore<N> {
+        ArrayStore::new()
+    }
+}
+
+impl<B: AsRef<[u8]>> FixedStore<B> {
+    /// Returns the data held, in the order of the stream.
+    fn parts(&self) -> [&[u8]; 2] {
+        let buffer = self.buffer.as_ref();
+        let first = min(self.len, buffer.len() - self.head);
+        [
+            &buffer[self.head..self.head + first],
+            &buffer[..self.len - first],
+        ]
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> CacheStore for FixedStore<B> {
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        let capacity = self.buffer.as_ref().len();
+        let end = self.end() + data.len() as u64;
+        // Only the end of the data fits, if at all
+        let data = &data[data.len().saturating_sub(capacity)..];
+        let overflow = (self.len + data.len()).saturating_sub(capacity);
+        if overflow > 0 {
+            self.head = (self.head + overflow) % capacity;
+            self.len -= overflow;
+        }
+        let tail = (self.head + self.len) % capacity.max(1);
+        let first = min(data.len(), capacity - tail);
+        let buffer = self.buffer.as_mut();
+        buffer[tail..tail + first].copy_from_slice(&data[..first]);
+        buffer[..data.len() - first].copy_from_slice(&data[first..]);
+        self.len += data.len();
+        self.start = end - self.len as u64;
+        Ok(())
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        if offset < self.start || offset >= self.end() {
+            return Ok(0);
+        }
+        Ok(copy_from_parts(
+            self.parts(),
+            (offset - self.start) as usize,
+            buf,
+        ))
+    }
+
+    fn chunk_at(&mut self, offset: u64) -> Result<&[u8]> {
+        if offset < self.start || offset >= self.end() {
+            return Ok(&[]);
+        }
+        let skip = (offset - self.start) as usize;
+        let [front, back] = self.parts();
+        if skip < front.len() {
+            Ok(&front[skip..])
+        } else {
+            Ok(&back[skip - front.len()..])
+        }
+    }
+
+    fn slice_at(&mut self, offset: u64, len: usize) -> Result<&[u8]> {
+        if offset < self.start || offset >= self.end() {
+            return Ok(&[]);
+        }
+        let skip = (offset - self.start) as usize;
+        let len = min(len, self.len - skip);
+        if skip + len > self.parts()[0].len() {
+            // Rotating in place makes the data contiguous without allocating
+            self.buffer.as_mut().rotate_left(self.head);
+            self.head = 0;
+        }
+        Ok(&self.parts()[0][skip..skip + len])
+    }
+
+    fn evict_before(&mut self, offset: u64) -> Result<()> {
+        let evicted = min(offset.saturating_sub(self.start), self.len());
+        if evicted > 0 {
+            self.head = (self.head + evicted as usize) % self.buffer.as_ref().len();
+            self.len -= evicted as usize;
+            self.start += evicted;
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self, offset: u64) -> Result<()> {
+        self.head = 0;
+        self.len = 0;
+        self.start = offset;
+        Ok(())
+    }
+
+    fn len(&self) -> u64 {
+        self.len as u64
+    }
+
+    fn start(&self) -> u64 {
+        self.start
+    }
+
+    fn capacity(&self) -> Option<u64> {
+        Some(self.buffer.as_ref().len() as u64)
+    }
+}
+
+/// In-memory store which keeps the whole stream
+///
+/// Seeking backwards always succeeds, but memory usage grows with the stream.
+#[derive(Debug, Default)]
+pub struct VecStore {
+    data: Vec<u8>,
+    start: u64,
+}
+
+impl VecStore {
+    /// Creates an empty store.
+    pub fn new() -> VecStore {
+        VecStore::default()
+    }
+}
+
+impl CacheStore for VecStore {
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        self.data.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let data = self.chunk_at(offset)?;
+        let len = min(data.len(), buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn chunk_at(&mut self, offset: u64) -> Result<&[u8]> {
+        if offset < self.start {
+            return Ok(&[]);
+        }
+        let skip = (offset - self.start) as usize;
+        Ok(self.data.get(skip..).unwrap_or_default())
+    }
+
+    fn slice_at(&mut self, offset: u64, len: usize) -> Result<&[u8]> {
+        let data = self.chunk_at(offset)?;
+        Ok(&data[..min(len, data.len())])
+    }
+
+    fn evict_before(&mut self, _offset: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn reset(&mut self, offset: u64) -> Result<()> {
+        self.data.clear();
+        self.start = offset;
+        Ok(())
+    }
+
+    fn len(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn start(&self) -> u64 {
+        self.start
+    }
+}
+
+/// In-memory store which keeps old data in blocks, and discards the least recently used ones
+///
+/// Unlike the other stores, this one can hold several separate parts of the stream,
+/// which suits reading from a few regions again and again, like an index and the records
+/// it points to. The data the reader still needs is always kept, even beyond the budget.
+#[cfg_attr(feature = "std", doc = " ```")]
+#[cfg_attr(not(feature = "std"), doc = " ```ignore")]
+/// use std::io::{Read, Seek, SeekFrom};
+/// use seekable_reader::{BlockStore, SeekableReader};
+///
+/// let source: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+/// let store = BlockStore::new(256, 4096);
+/// let mut reader = SeekableReader::with_store(source.as_slice(), 64, store);
+/// let mut index = [0; 16];
+/// reader.read_exact(&mut index).unwrap();
+/// for offset in [1000, 3000, 5000] {
+///     reader.seek(SeekFrom::Start(offset)).unwrap();
+///     reader.read_exact(&mut [0; 16]).unwrap();
+///     // The start of the stream stays cached, because it is used again and again
+///     reader.seek(SeekFrom::Start(0)).unwrap();
+///     reader.read_exact(&mut index).unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BlockStore {
+    block_size: usize,
+    budget: u64,
+    /// The blocks, by the stream position of their first byte
+    blocks: BTreeMap<u64, Block>,
+    len: u64,
+    end: u64,
+    /// Blocks reaching this stream position are still needed by the reader
+    needed_from: u64,
+    /// Counts the accesses to blocks, to find the least recently used one
+    clock: u64,
+    /// The stream positions of the blocks, by when they were used last
+    lru: BTreeMap<u64, u64>,
+    /// Holds data from several blocks for `slice_at`
+    slice: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct Block {
+    data: Vec<u8>,
+    last_used: u64,
+}
+
+impl BlockStore {
+    /// Creates an empty store, which keeps old data in blocks of `block_size` bytes,
+    /// as long as it holds no more than `budget` bytes.
+    pub fn new(block_size: usize, budget: u64) -> BlockStore {
+        BlockStore {
+            block_size: block_size.max(1),
+            budget,
+            blocks: BTreeMap::new(),
+            len: 0,
+            end: 0,
+            needed_from: 0,
+            clock: 0,
+            lru: BTreeMap::new(),
+            slice: Vec::new(),
+        }
+    }
+
+    /// Returns the block holding `offset`, if any, and marks it as used.
+    fn block_at(&mut self, offset: u64) -> Option<(u64, &[u8])> {
+        let (&start, block) = self.blocks.range_mut(..=offset).next_back()?;
+        if offset >= start + block.data.len() as u64 {
+            return None;
+        }
+        if block.last_used != self.clock {
+            self.clock += 1;
+            self.lru.remove(&block.last_used);
+            self.lru.insert(self.clock, start);
+            block.last_used = self.clock;
+        }
+        Some((start, &block.data))
+    }
+
+    /// Discards the least recently used blocks which are no longer needed, until the budget is met.
+    fn enforce_budget(&mut self) {
+        while self.len > self.budget {
+            // The blocks still needed are the last ones, which are rarely among the least recently used
+            let unneeded = self.lru.iter().find(|(_, start)| {
+                let block = &self.blocks[start];
+                *start + block.data.len() as u64 <= self.needed_from
+            });
+            let Some((&last_used, &start)) = unneeded else {
+                return;
+            };
+            self.lru.remove(&last_used);
+            let block = self
+                .blocks
+                .remove(&start)
+                .expect("every used block is in the store");
+            self.len -= block.data.len() as u64;
+        }
+    }
+}
+
+impl CacheStore for BlockStore {
+    fn append(&mut self, mut data: &[u8]) -> Result<()> {
+        while !data.is_empty() {
+            let last = self
+                .blocks
+                .last_entry()
+                .filter(|last| *last.key() + last.get().data.len() as u64 == self.end)
+                .filter(|last| last.get().data.len() < self.block_size);
+            let block = match last {
+                Some(last) => last.into_mut(),
+                None => {
+                    self.clock += 1;
+                    self.lru.insert(self.clock, self.end);
+                    self.blocks.entry(self.end).or_insert(Block {
+                        data: Vec::with_capacity(self.block_size),
+                        last_used: self.clock,
+                    })
+                }
+            };
+            let len = min(data.len(), self.block_size - block.data.len());
+            block.data.extend_from_slice(&data[..len]);
+            data = &data[len..];
+            self.len += len as u64;
+            self.end += len as u64;
+        }
+        self.enforce_budget();
+        Ok(())
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let mut copied = 0;
+        while copied < buf.len() {
+            let data = self.chunk_at(offset + copied as u64)?;
+            if data.is_empty() {
+                break;
+            }
+            let len = min(data.len(), buf.len() - copied);
+            buf[copied..copied + len].copy_from_slice(&data[..len]);
+            copied += len;
+        }
+        Ok(copied)
+    }
+
+    fn chunk_at(&mut self, offset: u64) -> Result<&[u8]> {
+        match self.block_at(offset) {
+            Some((start, data)) => Ok(&data[(offset - start) as usize..]),
+            None => Ok(&[]),
+        }
+    }
+
+    fn slice_at(&mut self, offset: u64, len: usize) -> Result<&[u8]> {
+        let end = self
+            .held_until(offset, offset + len as u64)
+            .unwrap_or(offset);
+        let mut slice = core::mem::take(&mut self.slice);
+        slice.resize((end - offset) as usize, 0);
+        self.read_at(offset, &mut slice)?;
+        self.slice = slice;
+        Ok(&self.slice)
+    }
+
+    fn evict_before(&mut self, offset: u64) -> Result<()> {
+        self.needed_from = offset;
+        self.enforce_budget();
+        Ok(())
+    }
+
+    fn reset(&mut self, offset: u64) -> Result<()> {
+        self.blocks.clear();
+        self.lru.clear();
+        self.len = 0;
+        self.end = offset;
+        self.needed_from = offset;
+        Ok(())
+    }
+
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn start(&self) -> u64 {
+        self.blocks.keys().next().copied().unwrap_or(self.end)
+    }
+
+    fn end(&self) -> u64 {
+        self.end
+    }
+
+    fn held_until(&self, offset: u64, limit: u64) -> Option<u64> {
+        let (&start, block) = self.blocks.range(..=offset).next_back()?;
+        let mut end = start + block.data.len() as u64;
+        if offset >= end {
+            return None;
+        }
+        for (&start, block) in self.blocks.range(end..) {
+            if start != end || end >= limit {
+                break;
+            }
+            end += block.data.len() as u64;
+        }
+        Some(min(end, limit))
+    }
+}
+
+/// Store which keeps the reader's window in memory and spills older data into a temporary file
+///
+/// Like a spooled temporary file, the memory part is the fast path, while the file
+/// allows seeking back to every position since the start of the stream.
+/// The file is anonymous and removed when the store is dropped.
+#[cfg(feature = "tempfile")]
+#[derive(Debug)]
+pub struct TempFileStore {
+    file: File,
+    /// Stream position of the first byte in the file
+    start: u64,
+    memory: RingStore,
+    /// Holds data read back from the file for `chunk_at`
+    chunk: Vec<u8>,
+}
+
+/// How much `TempFileStore::chunk_at` reads from the file at once
+#[cfg(feature = "tempfile")]
+const FILE_CHUNK_SIZE: u64 = 8 * 1024;
+
+#[cfg(feature = "tempfile")]
+impl TempFileStore {
+    /// Creates an empty store, backed by a new temporary file.
+    pub fn new() -> Result<TempFileStore> {
+        Ok(TempFileStore {
+            file: tempfile::tempfile()?,
+            start: 0,
+            memory: RingStore::new(),
+            chunk: Vec::new(),
+        })
+    }
+}
+
+#[cfg(feature = "tempfile")]
+impl CacheStore for TempFileStore {
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        self.memory.append(data)
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let spilled = self.memory.start();
+        if offset >= spilled {
+            return self.memory.read_at(offset, buf);
+        }
+        if offset < self.start {
+            return Ok(0);
+        }
+        let len = min((spilled - offset) as usize, buf.len());
+        self.file.seek(SeekFrom::Start(offset - self.start))?;
+        self.file.read_exact(&mut buf[..len])?;
+        Ok(len)
+    }
+
+    fn chunk_at(&mut self, offset: u64) -> Result<&[u8]> {
+        let spilled = self.memory.start();
+        if offset >= spilled {
+            return self.memory.chunk_at(offset);
+        }
+        if offset < self.start {
+            return Ok(&[]);
+        }
+        let mut chunk = core::mem::take(&mut self.chunk);
+        chunk.resize(min(spilled - offset, FILE_CHUNK_SIZE) as usize, 0);
+        self.read_at(offset, &mut chunk)?;
+        self.chunk = chunk;
+        Ok(&self.chunk)
+    }
+
+    fn slice_at(&mut self, offset: u64, len: usize) -> Result<&[u8]> {
+        if offset >= self.memory.start() {
+            return self.memory.slice_at(offset, len);
+        }
+        if offset < self.start {
+            return Ok(&[]);
+        }
+        let mut chunk = core::mem::take(&mut self.chunk);
+        chunk.resize(min(self.end() - offset, len as u64) as usize, 0);
+        let mut copied = 0;
+        while copied < chunk.len() {
+            copied += self.read_at(offset + copied as u64, &mut chunk[copied..])?;
+        }
+        self.chunk = chunk;
+        Ok(&self.chunk)
+    }
+
+    fn append_and_evict(&mut self, data: &[u8], offset: u64) -> Result<()> {
+        // Data which would be spilled right away is written to the file directly
+        let end = self.memory.end();
+        let spill = min(offset.saturating_sub(end), data.len() as u64) as usize;
+        if spill > 0 {
+            self.evict_before(end)?;
+            self.file.seek(SeekFrom::End(0))?;
+            self.file.write_all(&data[..spill])?;
+            self.memory.reset(end + spill as u64)?;
+        }
+        self.memory.append(&data[spill..])?;
+        self.evict_before(offset)
+    }
+
+    fn evict_before(&mut self, offset: u64) -> Result<()> {
+        let offset = min(offset, self.memory.end());
+        let spilled = self.memory.start();
+        if offset <= spilled {
+            return Ok(());
+        }
+        let mut len = (offset - spilled) as usize;
+        self.file.seek(SeekFrom::End(0))?;
+        let (front, back) = self.memory.data.as_slices();
+        for part in [front, back] {
+            let part = &part[..min(len, part.len())];
+            self.file.write_all(part)?;
+            len -= part.len();
+        }
+        self.memory.evict_before(offset)
+    }
+
+    fn reset(&mut self, offset: u64) -> Result<()> {
+        self.file.set_len(0)?;
+        self.start = offset;
+        self.memory.reset(offset)
+    }
+
+    fn len(&self) -> u64 {
+        self.memory.end() - self.start
+    }
+
+    fn start(&self) -> u64 {
+        self.start
+    }
+}
+
+/// Store which keeps the whole stream in a memory-mapped temporary file
+///
+/// The operating system decides which parts stay in memory, so this suits
+/// streams that are too large for a [`VecStore`] but are read from all over.
+/// The file is anonymous and removed when the store is dropped.
+#[cfg(feature = "mmap")]
+#[derive(Debug)]
+pub struct MmapStore {
+    file: File,
+    map: memmap2::MmapMut,
+    len: usize,
+    start: u64,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapStore {
+    /// Creates an empty store, backed by a new temporary file.
+    pub fn new() -> Result<MmapStore> {
+        MmapStore::with_capacity(64 * 1024)
+    }
+
+    /// Creates an empty store with room for `capacity` bytes before the mapping has to grow.
+    pub fn with_capacity(capacity: usize) -> Result<MmapStore> {
+        let file = tempfile::tempfile()?;
+        let map = MmapStore::map(&file, capacity.max(1))?;
+        Ok(MmapStore {
+            file,
+            map,
+            len: 0,
+            start: 0,
+        })
+    }
+
+    fn map(file: &File, capacity: usize) -> Result<memmap2::MmapMut> {
+        file.set_len(capacity as u64)?;
+        // SAFETY: The file is an anonymous temporary file, so nobody else can modify it
+        // while it is mapped.
+        unsafe { memmap2::MmapMut::map_mut(file) }
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl CacheStore for MmapStore {
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        let needed = self.len + data.len();
+        if needed > self.map.len() {
+            self.map = MmapStore::map(&self.file, needed.max(2 * self.map.len()))?;
+        }
+        self.map[self.len..needed].copy_from_slice(data);
+        self.len = needed;
+        Ok(())
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let data = self.chunk_at(offset)?;
+        let len = min(data.len(), buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+
+    fn chunk_at(&mut self, offset: u64) -> Result<&[u8]> {
+        if offset < self.start {
+            return Ok(&[]);
+        }
+        let skip = (offset - self.start) as usize;
+        Ok(self.map[..self.len].get(skip..).unwrap_or_default())
+    }
+
+    fn slice_at(&mut self, offset: u64, len: usize) -> Result<&[u8]> {
+        let data = self.chunk_at(offset)?;
+        Ok(&data[..min(len, data.len())])
+    }
+
+    fn evict_before(&mut self, _offset: u64) -> Result<()> {
+        Ok(())
+    }
+
+    fn reset(&mut self, offset: u64) -> Result<()> {
+        self.len = 0;
+        self.start = offset;
+        Ok(())
+    }
+
+    fn len(&self) -> u64 {
+        self.len as u64
+    }
+
+    fn start(&self) -> u64 {
+        self.start
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn check_store<S: CacheStore>(store: &mut S, discards: bool) {
+        let data: Vec<u8> = (0..100).collect();
+        for chunk in data.chunks(7) {
+            store.append(chunk).unwrap();
+        }
+        assert_eq!(store.end(), 100);
+        store.evict_before(60).unwrap();
+        assert_eq!(store.start(), if discards { 60 } else { 0 });
+        let mut buf = [0; 30];
+        assert_eq!(store.read_at(80, &mut buf).unwrap(), 20);
+        assert_eq!(&buf[..20], &data[80..]);
+        assert_eq!(store.read_at(100, &mut buf).unwrap(), 0);
+        assert_eq!(store.chunk_at(99).unwrap(), &[99]);
+        assert!(store.chunk_at(100).unwrap().is_empty());
+        assert_eq!(store.slice_at(90, 20).unwrap(), &data[90..]);
+        assert_eq!(store.slice_at(61, 30).unwrap(), &data[61..91]);
+        assert!(store.slice_at(100, 1).unwrap().is_empty());
+        if !discards {
+            let mut buf = [0; 100];
+            let mut pos = 0;
+            while pos < 100 {
+                pos += store.read_at(pos as u64, &mut buf[pos..]).unwrap();
+            }
+            assert_eq!(store.chunk_at(0).unwrap()[0], 0);
+            assert_eq!(store.slice_at(10, 80).unwrap(), &data[10..90]);
+            assert_eq!(&buf[..], &data[..]);
+        }
+    }
+
+    #[test]
+    fn ring_store() {
+        let mut store = RingStore::with_capacity(10);
+        check_store(&mut store, true);
+        assert_eq!(store.len(), 40);
+    }
+
+    #[test]
+    fn ring_store_skips_evicted_data() {
+        let mut store = RingStore::with_capacity(32);
+        let data: Vec<u8> = (0..=255).cycle().take(1 << 20).collect();
+        store.append(&data[..10]).unwrap();
+        store.append_and_evict(&data[10..], (1 << 20) - 32).unwrap();
+        assert_eq!(store.start(), (1 << 20) - 32);
+        assert_eq!(
+            store.slice_at(store.start(), 32).unwrap(),
+            &data[(1 << 20) - 32..]
+        );
+        assert!(store.data.capacity() < 1024);
+    }
+
+    #[test]
+    fn fixed_store() {
+        let mut store = ArrayStore::<50>::new();
+        check_store(&mut store, true);
+        assert_eq!(store.len(), 40);
+        let mut buffer = [0; 45];
+        check_store(&mut SliceStore::new(&mut buffer), true);
+        // Data beyond the capacity is discarded, oldest first
+        let mut store = ArrayStore::<8>::new();
+        let data: Vec<u8> = (0..20).collect();
+        store.append(&data[..5]).unwrap();
+        store.append(&data[5..]).unwrap();
+        assert_eq!(store.start(), 12);
+        assert_eq!(store.slice_at(12, 8).unwrap(), &data[12..]);
+        store.append(&data[..3]).unwrap();
+        assert_eq!(store.start(), 15);
+        assert_eq!(
+            store.slice_at(15, 8).unwrap(),
+            &[15, 16, 17, 18, 19, 0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn vec_store() {
+        check_store(&mut VecStore::new(), false);
+    }
+
+    #[test]
+    fn block_store() {
+        check_store(&mut BlockStore::new(16, 100), false);
+        let mut store = BlockStore::new(16, 64);
+        let data: Vec<u8> = (0..100).collect();
+        store.append(&data[..48]).unwrap();
+        store.evict_before(48).unwrap();
+        // Use the first block, so the second one is the least recently used
+        assert_eq!(store.chunk_at(3).unwrap(), &data[3..16]);
+        store.append(&data[48..80]).unwrap();
+        assert_eq!(store.len(), 64);
+        assert_eq!(store.held_until(0, u64::MAX), Some(16));
+        assert_eq!(store.held_until(20, u64::MAX), None);
+        assert_eq!(store.held_until(32, u64::MAX), Some(80));
+        assert_eq!(store.held_until(32, 50), Some(50));
+        assert_eq!(store.slice_at(40, 60).unwrap(), &data[40..80]);
+        // Data that is still needed is kept beyond the budget
+        store.evict_before(0).unwrap();
+        store.append(&data[80..]).unwrap();
+        assert_eq!(store.len(), 84);
+    }
+
+    #[cfg(feature = "tempfile")]
+    #[test]
+    fn temp_file_store() {
+        check_store(&mut TempFileStore::new().unwrap(), false);
+        let mut store = TempFileStore::new().unwrap();
+        let data: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+        store.append(&data[..10]).unwrap();
+        store.append_and_evict(&data[10..], 99_990).unwrap();
+        assert_eq!(store.memory.len(), 10);
+        assert_eq!(store.slice_at(5, 99_995).unwrap(), &data[5..]);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_store() {
+        check_store(&mut MmapStore::with_capacity(16).unwrap(), false);
+    }
+}
@@ -0,0 +1,408 @@
+//! The window and position logic shared by all reader types.
+//!
+//! The [`Window`] never touches the inner reader itself. The reader types read from
+//! their inner reader in whatever way fits them and hand the data to the window.
+use crate::error::{
+    beyond_len_hint, negative_seek, seek_beyond_len, tail_beyond_capacity, unexpected_eof,
+};
+use crate::io::{self, Result, SeekFrom};
+use crate::{CacheStore, OutOfWindowPolicy, SeekOutOfWindow};
+use alloc::vec::Vec;
+use core::cmp::{max, min};
+use core::ops::Range;
+
+/// How a seek can be completed
+pub(crate) enum Seek {
+    /// The seek is done, this is the new stream position.
+    Done(u64),
+    /// The target lies ahead of the data read so far.
+    /// Data has to be read until the target is reached, or until EOF.
+    Forward(u64),
+    /// The stream length is not known yet.
+    /// The inner reader has to be read until EOF, then call [`Window::seek_from_end`].
+    End(i64),
+    /// The target is no longer cached, but the inner reader may be recreated to reach it.
+    /// Readers that cannot do this fail with [`Window::out_of_window_error`].
+    Reopen(u64),
+}
+
+/// The cached data of a stream and the stream position
+pub(crate) struct Window<S: CacheStore> {
+    pub keep_size: usize,
+    store: S,
+    /// Stream position of the reader
+    pos: u64,
+    /// Stream position of the inner reader, the end of all data read so far
+    read_bytes: u64,
+    /// Total length of the stream, known once the inner reader reached EOF
+    stream_len: Option<u64>,
+    /// Length of the stream as told by the user, see [`Window::set_len_hint`]
+    len_hint: Option<u64>,
+    /// Stream position from which on data must not be evicted, see [`Window::pin`]
+    pinned: Option<u64>,
+    /// Stream position from which on data must not be evicted, and how many bytes
+    /// before the end of the data this may be at most, see [`Window::retain`]
+    retained: Option<(u64, u64)>,
+    /// Copy of the first bytes of the stream, which is never evicted
+    head: Vec<u8>,
+    /// How many bytes `head` is supposed to hold
+    head_len: usize,
+    /// How many bytes at the end of the stream are never evicted, see [`Window::set_tail_len`]
+    tail_len: usize,
+    pub out_of_window: OutOfWindowPolicy,
+}
+
+impl<S: CacheStore> Window<S> {
+    pub const fn new(keep_size: usize, store: S) -> Window<S> {
+        Window {
+            keep_size,
+            store,
+            pos: 0,
+            read_bytes: 0,
+            stream_len: None,
+            len_hint: None,
+            pinned: None,
+            retained: None,
+            head: Vec::new(),
+            head_len: 0,
+            tail_len: 0,
+            out_of_window: OutOfWindowPolicy::Error,
+        }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn pos(&self) -> u64 {
+        self.pos
+    }
+
+    pub fn read_bytes(&self) -> u64 {
+        self.read_bytes
+    }
+
+    pub fn stream_len(&self) -> Option<u64> {
+        self.stream_len
+    }
+
+    /// Returns the stream positions of the cached data.
+    pub fn range(&self) -> Range<u64> {
+        self.store.start()..self.read_bytes
+    }
+
+    /// Returns whether the stream position is behind all data read so far.
+    pub fn at_end(&self) -> bool {
+        self.pos == self.read_bytes
+    }
+
+    pub fn set_stream_len(&mut self, stream_len: u64) {
+        self.stream_len = Some(stream_len);
+    }
+
+    /// Sets the length the stream is expected to have.
+    ///
+    /// Seeks beyond it fail, an inner reader ending before it fails with `UnexpectedEof`,
+    /// and one going on after it with `InvalidData`.
+    pub fn set_len_hint(&mut self, len: u64) {
+        self.len_hint = Some(len);
+        self.stream_len = Some(len);
+    }
+
+    /// Returns whether everything up to the end of the stream was read.
+    pub fn at_eof(&self) -> bool {
+        self.stream_len == Some(self.read_bytes)
+    }
+
+    /// Keeps the first `len` bytes of the stream for good, see [`Window::head`].
+    ///
+    /// If the beginning of the stream was already evicted, the head is filled
+    /// once the inner reader starts over.
+    pub fn set_head_len(&mut self, len: usize) -> Result<()> {
+        self.head_len = len;
+        self.head.clear();
+        let held = self.store.held_until(0, len as u64).unwrap_or_default();
+        self.head.resize(held as usize, 0);
+        let mut copied = 0;
+        while copied < self.head.len() {
+            copied += self
+                .store
+                .read_at(copied as u64, &mut self.head[copied..])?;
+        }
+        Ok(())
+    }
+
+    /// Keeps the last `len` bytes of the stream, once they were read.
+    ///
+    /// Until the length of the stream is known, the last `len` bytes read so far are kept.
+    pub fn set_tail_len(&mut self, len: usize) -> Result<()> {
+        if self
+            .store
+            .capacity()
+            .is_some_and(|capacity| len as u64 > capacity)
+        {
+            return Err(tail_beyond_capacity());
+        }
+        self.tail_len = len;
+        Ok(())
+    }
+
+    /// Returns the pinned head at the stream position, if the stream position lies within it.
+    fn head(&self) -> Option<&[u8]> {
+        usize::try_from(self.pos)
+            .ok()
+            .and_then(|pos| self.head.get(pos..))
+            .filter(|head| !head.is_empty())
+    }
+
+    /// Returns whether the data at `offset` is cached, in the store or in the head,
+    /// or whether `offset` is where the inner reader continues.
+    fn is_cached(&self, offset: u64) -> bool {
+        offset == self.read_bytes
+            || offset < self.head.len() as u64
+            || self.store.held_until(offset, offset + 1).is_some()
+    }
+
+    /// Returns whether the data at the stream position is not cached, which happens
+    /// when reading beyond the end of the head, or of a block in a block cache.
+    pub fn in_gap(&self) -> bool {
+        self.reaches_gap(1)
+    }
+
+    /// Returns whether some of the next `len` bytes, as far as they were read, are not cached
+    /// in one piece, either in the head or in the store.
+    pub fn reaches_gap(&self, len: usize) -> bool {
+        let end = min(self.pos.saturating_add(len as u64), self.read_bytes);
+        let held = self.store.held_until(self.pos, end).unwrap_or_default();
+        end > self.pos && end > self.head.len() as u64 && end > held
+    }
+
+    /// Returns the cached data at the stream position.
+    pub fn cached(&mut self) -> Result<&[u8]> {
+        if self.at_end() {
+            return Ok(&[]);
+        }
+        if self.in_gap() {
+            return Err(self.out_of_window_error(self.pos));
+        }
+        match usize::try_from(self.pos)
+            .ok()
+            .and_then(|pos| self.head.get(pos..))
+        {
+            Some(head) if !head.is_empty() => Ok(head),
+            _ => self.store.chunk_at(self.pos),
+        }
+    }
+
+    /// Copies cached data at the stream position into `buf` and moves the position behind it.
+    pub fn read_cached(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.at_end() {
+            return Ok(0);
+        }
+        if self.in_gap() {
+            return Err(self.out_of_window_error(self.pos));
+        }
+        let read = match self.head() {
+            Some(head) => {
+                let len = min(head.len(), buf.len());
+                buf[..len].copy_from_slice(&head[..len]);
+                len
+            }
+            None => self.store.read_at(self.pos, buf)?,
+        };
+        self.pos += read as u64;
+        Ok(read)
+    }
+
+    /// Returns up to `len` cached bytes at the stream position, without moving it.
+    ///
+    /// Within the head, the data ends early where the head ends, see [`Window::reaches_gap`].
+    pub fn peek(&mut self, len: usize) -> Result<&[u8]> {
+        if self.store.held_until(self.pos, self.pos + 1).is_none() {
+            let head = self.head().unwrap_or_default();
+            return Ok(&head[..min(len, head.len())]);
+        }
+        self.store.slice_at(self.pos, len)
+    }
+
+    pub fn consume(&mut self, amt: usize) {
+        self.pos = min(self.pos + amt as u64, self.read_bytes);
+    }
+
+    /// Appends `data`, which was read from the inner reader into a buffer of `requested` bytes.
+    ///
+    /// An empty read into a non-empty buffer marks the end of the stream.
+    /// The stream position is not changed.
+    pub fn append(&mut self, data: &[u8], requested: usize) -> Result<()> {
+        self.push(data, requested, self.pos)
+    }
+
+    /// Like [`Window::append`], but for data that was already handed to the user.
+    ///
+    /// The stream position is moved behind the data.
+    pub fn append_consumed(&mut self, data: &[u8], requested: usize) -> Result<()> {
+        self.push(data, requested, self.read_bytes + data.len() as u64)
+    }
+
+    /// Appends `data` and moves the stream position to `pos`.
+    ///
+    /// The store learns right away which data is no longer needed, so it does not
+    /// have to take in the part of a large read it would discard anyway.
+    fn push(&mut self, data: &[u8], requested: usize, pos: u64) -> Result<()> {
+        if data.is_empty() && requested > 0 {
+            if self.stream_len.is_some_and(|len| self.read_bytes < len) {
+                return Err(unexpected_eof(
+                    "the stream ended before its expected length",
+                ));
+            }
+            self.stream_len.get_or_insert(self.read_bytes);
+        }
+        let read_bytes = self.read_bytes + data.len() as u64;
+        if self.len_hint.is_some_and(|len| read_bytes > len) {
+            return Err(beyond_len_hint());
+        }
+        if self.head.len() as u64 == self.read_bytes && self.head.len() < self.head_len {
+            let len = min(data.len(), self.head_len - self.head.len());
+            self.head.extend_from_slice(&data[..len]);
+        }
+        let keep_from = self.keep_from(pos, read_bytes);
+        self.store.append_and_evict(data, keep_from)?;
+        self.read_bytes = read_bytes;
+        self.pos = pos;
+        Ok(())
+    }
+
+    /// Returns before which stream position the store may drop the data, given the stream
+    /// position `pos` and the end `read_bytes` of the data read.
+    ///
+    /// That is everything before the last `2 * keep_size` bytes, but never data at or after
+    /// the stream position, a pinned or the retained position, nor the tail of the stream.
+    fn keep_from(&self, pos: u64, read_bytes: u64) -> u64 {
+        let keep_size = 2 * self.keep_size as u64;
+        let keep_from = min(pos, read_bytes.saturating_sub(keep_size));
+        let end = max(self.stream_len.unwrap_or_default(), read_bytes);
+        let keep_from = min(keep_from, end.saturating_sub(self.tail_len as u64));
+        let keep_from = match self.retained {
+            Some((from, limit)) => min(keep_from, max(from, read_bytes.saturating_sub(limit))),
+            None => keep_from,
+        };
+        self.pinned
+            .map_or(keep_from, |pinned| min(keep_from, pinned))
+    }
+
+    /// Keeps the data from the stream position on, until [`Window::unpin`] is called
+    /// with the returned previous pin.
+    ///
+    /// Pins nest, so they are released in the reverse order, which needs no allocation.
+    pub fn pin(&mut self) -> Option<u64> {
+        let previous = self.pinned;
+        self.pinned = Some(previous.map_or(self.pos, |pinned| min(pinned, self.pos)));
+        previous
+    }
+
+    pub fn unpin(&mut self, previous: Option<u64>) {
+        self.pinned = previous;
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.pinned.is_some()
+    }
+
+    /// Keeps the data from `offset` on, besides the data the stream position needs,
+    /// but not more than the last `limit` bytes. Everything before is evicted with the next append.
+    #[cfg(feature = "std")]
+    pub fn retain(&mut self, offset: u64, limit: u64) {
+        self.retained = Some((offset, limit));
+    }
+
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<Seek> {
+        match pos {
+            SeekFrom::Start(target) => self.seek_to(Some(target)),
+            SeekFrom::Current(shift) => self.seek_to(self.pos.checked_add_signed(shift)),
+            SeekFrom::End(shift) => match self.stream_len {
+                Some(stream_len) => self.seek_to(stream_len.checked_add_signed(shift)),
+                None => Ok(Seek::End(shift)),
+            },
+        }
+    }
+
+    /// Seeks to `target`, which is `None` if it lies before the start of the stream.
+    fn seek_to(&mut self, target: Option<u64>) -> Result<Seek> {
+        let earliest = self.store.start();
+        let Some(target) = target else {
+            if self.out_of_window == OutOfWindowPolicy::Clamp {
+                self.pos = if self.head.is_empty() { earliest } else { 0 };
+                return Ok(Seek::Done(self.pos));
+            }
+            return Err(negative_seek());
+        };
+        if target > self.read_bytes {
+            if self.len_hint.is_some_and(|len| target > len) {
+                return Err(seek_beyond_len());
+            }
+            // There is nothing to read beyond the end of the stream
+            let target = match self.stream_len {
+                Some(len) => max(min(target, len), self.read_bytes),
+                None => target,
+            };
+            if target == self.read_bytes {
+                self.pos = target;
+                return Ok(Seek::Done(self.pos));
+            }
+            return Ok(Seek::Forward(target));
+        }
+        if !self.is_cached(target) {
+            return match self.out_of_window {
+                OutOfWindowPolicy::Error => Err(self.out_of_window_error(target)),
+                OutOfWindowPolicy::Clamp => {
+                    self.pos = earliest;
+                    Ok(Seek::Done(self.pos))
+                }
+                OutOfWindowPolicy::Reopen => Ok(Seek::Reopen(target)),
+            };
+        }
+        self.pos = target;
+        Ok(Seek::Done(self.pos))
+    }
+
+    pub fn out_of_window_error(&self, target: u64) -> io::Error {
+        SeekOutOfWindow {
+            requested: target,
+            earliest_available: self.store.start(),
+        }
+        .into()
+    }
+
+    /// Discards all cached data, because the inner reader continues at the stream position `offset`.
+    pub fn reset(&mut self, offset: u64) -> Result<()> {
+        self.store.reset(offset)?;
+        self.pos = offset;
+        self.read_bytes = offset;
+        Ok(())
+    }
+
+    /// Completes a [`Seek::End`] once the inner reader reached EOF.
+    ///
+    /// The result is [`Seek::Done`], or [`Seek::Reopen`] if the target is no longer cached.
+    /// If the seek fails and reading until EOF evicted `old_position`,
+    /// the stream position is left at the end of the stream.
+    pub fn seek_from_end(&mut self, shift: i64, old_position: u64) -> Result<Seek> {
+        match self.seek(SeekFrom::End(shift)) {
+            Ok(Seek::Forward(_) | Seek::End(_)) => {
+                unreachable!("the stream length is known at EOF")
+            }
+            Ok(seek) => Ok(seek),
+            Err(err) => {
+                self.return_to(old_position);
+                Err(err)
+            }
+        }
+    }
+
+    /// Moves the stream position back to `old_position` after a failed seek, if it is still cached.
+    pub fn return_to(&mut self, old_position: u64) {
+        if self.is_cached(old_position) {
+            self.pos = old_position;
+        }
+    }
+}
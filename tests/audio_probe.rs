@@ -1,3 +1,5 @@
+#![cfg(feature = "std")]
+
 use seekable_reader::SeekableReader;
 /// Real world example of seek and read operations obtained through an observer and rodio-rs
 use std::io::{Read, Result, Seek, SeekFrom};
@@ -22,23 +24,23 @@ fn complex_read_seek() {
     let reader = ExampleRead { counter: 0 };
     let mut reader = SeekableReader::new(reader, 1_048_576);
     let mut buf = vec![0; 2048];
-    reader.seek(SeekFrom::Current(0)).unwrap();
-    reader.read(&mut buf[..4]).unwrap();
+    assert_eq!(reader.stream_position().unwrap(), 0);
+    assert_eq!(reader.read(&mut buf[..4]).unwrap(), 4);
     assert_eq!(buf[0..4], vec![0, 1, 2, 3]);
     reader.seek(SeekFrom::Start(0)).unwrap();
-    reader.seek(SeekFrom::Current(0)).unwrap();
-    reader.read(&mut buf[..2048]).unwrap();
-    for i in 0..2048 {
-        assert_eq!(buf[i], (i % 256) as u8);
+    assert_eq!(reader.stream_position().unwrap(), 0);
+    assert_eq!(reader.read(&mut buf[..2048]).unwrap(), 2048);
+    for (i, byte) in buf.iter().enumerate() {
+        assert_eq!(*byte, (i % 256) as u8);
     }
     reader.seek(SeekFrom::Start(0)).unwrap();
-    reader.seek(SeekFrom::Current(0)).unwrap();
-    reader.read(&mut buf[..27]).unwrap();
-    for i in 0..27 {
-        assert_eq!(buf[i], (i % 256) as u8);
+    assert_eq!(reader.stream_position().unwrap(), 0);
+    assert_eq!(reader.read(&mut buf[..27]).unwrap(), 27);
+    for (i, byte) in buf[..27].iter().enumerate() {
+        assert_eq!(*byte, (i % 256) as u8);
     }
-    reader.read(&mut buf[..1024]).unwrap();
-    for i in 0..1024 {
-        assert_eq!(buf[i], ((i + 27) % 256) as u8);
+    assert_eq!(reader.read(&mut buf[..1024]).unwrap(), 1024);
+    for (i, byte) in buf[..1024].iter().enumerate() {
+        assert_eq!(*byte, ((i + 27) % 256) as u8);
     }
 }
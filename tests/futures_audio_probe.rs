@@ -0,0 +1,137 @@
+#![cfg(feature = "futures-io")]
+
+use futures::executor::block_on;
+use futures::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeekExt};
+use futures::StreamExt;
+use seekable_reader::AsyncSeekableReader;
+/// The scenarios of `audio_probe.rs`, run against the futures-io reader
+use std::io::{ErrorKind, Result, SeekFrom};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// AsyncRead implementation
+struct ExampleRead {
+    counter: usize,
+}
+
+impl AsyncRead for ExampleRead {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        for byte in buf.iter_mut() {
+            *byte = self.counter as u8;
+            self.counter += 1;
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+}
+
+/// Counts up, but fails once at `fail_at`
+struct FailingRead {
+    counter: usize,
+    fail_at: Option<usize>,
+}
+
+impl AsyncRead for FailingRead {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize>> {
+        if self.fail_at == Some(self.counter) {
+            self.fail_at = None;
+            return Poll::Ready(Err(ErrorKind::BrokenPipe.into()));
+        }
+        let len = buf
+            .len()
+            .min(self.fail_at.unwrap_or(usize::MAX) - self.counter);
+        for byte in &mut buf[..len] {
+            *byte = self.counter as u8;
+            self.counter += 1;
+        }
+        Poll::Ready(Ok(len))
+    }
+}
+
+#[test]
+fn complex_read_seek() {
+    block_on(async {
+        let reader = ExampleRead { counter: 0 };
+        let mut reader = AsyncSeekableReader::new(reader, 1_048_576);
+        let mut buf = vec![0; 2048];
+        reader.seek(SeekFrom::Current(0)).await.unwrap();
+        reader.read_exact(&mut buf[..4]).await.unwrap();
+        assert_eq!(buf[0..4], vec![0, 1, 2, 3]);
+        reader.seek(SeekFrom::Start(0)).await.unwrap();
+        reader.seek(SeekFrom::Current(0)).await.unwrap();
+        reader.read_exact(&mut buf[..2048]).await.unwrap();
+        for (i, byte) in buf.iter().enumerate() {
+            assert_eq!(*byte, (i % 256) as u8);
+        }
+        reader.seek(SeekFrom::Start(0)).await.unwrap();
+        reader.seek(SeekFrom::Current(0)).await.unwrap();
+        reader.read_exact(&mut buf[..27]).await.unwrap();
+        for (i, byte) in buf[..27].iter().enumerate() {
+            assert_eq!(*byte, (i % 256) as u8);
+        }
+        reader.read_exact(&mut buf[..1024]).await.unwrap();
+        for (i, byte) in buf[..1024].iter().enumerate() {
+            assert_eq!(*byte, ((i + 27) % 256) as u8);
+        }
+    })
+}
+
+#[test]
+fn seek_forward_and_from_end() {
+    block_on(async {
+        let source: Vec<u8> = (0..100).collect();
+        let mut reader = AsyncSeekableReader::new(source.as_slice(), 10);
+        let mut buf = [0; 5];
+        assert_eq!(reader.seek(SeekFrom::Start(50)).await.unwrap(), 50);
+        reader.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [50, 51, 52, 53, 54]);
+        assert_eq!(reader.seek(SeekFrom::Current(-15)).await.unwrap(), 40);
+        assert!(reader.seek(SeekFrom::Start(0)).await.is_err());
+        assert_eq!(reader.seek(SeekFrom::End(-5)).await.unwrap(), 95);
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, [95, 96, 97, 98, 99]);
+        assert_eq!(reader.seek(SeekFrom::Start(200)).await.unwrap(), 100);
+    })
+}
+
+#[test]
+fn buf_read_lines() {
+    block_on(async {
+        let source = b"first line\nsecond line\n";
+        let mut reader = AsyncSeekableReader::new(&source[..], 4);
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "first line\n");
+        reader.seek(SeekFrom::Current(-5)).await.unwrap();
+        let mut lines = reader.lines();
+        assert_eq!(lines.next().await.unwrap().unwrap(), "line");
+        assert_eq!(lines.next().await.unwrap().unwrap(), "second line");
+    })
+}
+
+#[test]
+fn failed_seek_is_not_resumed() {
+    block_on(async {
+        let inner = FailingRead {
+            counter: 0,
+            fail_at: Some(20),
+        };
+        let mut reader = AsyncSeekableReader::new(inner, 8);
+        reader.read_exact(&mut [0; 10]).await.unwrap();
+        let err = reader.seek(SeekFrom::Current(100)).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+        // The failed seek stopped at byte 20, the same seek starts over from there
+        assert_eq!(reader.seek(SeekFrom::Current(100)).await.unwrap(), 120);
+        let mut byte = [0];
+        reader.read_exact(&mut byte).await.unwrap();
+        assert_eq!(byte, [120]);
+    })
+}
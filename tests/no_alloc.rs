@@ -0,0 +1,120 @@
+#![cfg(feature = "std")]
+
+use seekable_reader::{ArrayStore, SeekableReader};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io::{BufRead, Error, ErrorKind, Read, Seek, SeekFrom};
+
+/// Counts the allocations of the current thread
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+/// A stream of 64 KiB which counts up
+struct Counter(usize);
+
+impl Read for Counter {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(0x10000 - self.0);
+        for (n, byte) in buf[..len].iter_mut().enumerate() {
+            *byte = (self.0 + n) as u8;
+        }
+        self.0 += len;
+        Ok(len)
+    }
+}
+
+#[test]
+fn fixed_buffers_never_allocate() {
+    const READER: SeekableReader<&[u8], ArrayStore<4>> = SeekableReader::with_array(&[]);
+    assert_eq!(READER.keep_size(), 2);
+
+    let before = allocations();
+    let mut buffer = [0; 64];
+    let mut reader = SeekableReader::with_buffer(Counter(0), &mut buffer);
+    let mut data = [0; 16];
+    reader.read_exact(&mut data).unwrap();
+    reader.seek(SeekFrom::Current(-10)).unwrap();
+    reader.read_exact(&mut data).unwrap();
+    assert_eq!(data[0], 6);
+    reader.seek(SeekFrom::Start(40_000)).unwrap();
+    reader.seek(SeekFrom::Current(-32)).unwrap();
+    assert_eq!(reader.fill_buf().unwrap()[0], (40_000 - 32) as u8);
+    assert_eq!(reader.seek(SeekFrom::End(-1)).unwrap(), 0xffff);
+    assert_eq!(reader.peek(8).unwrap(), &[0xff]);
+
+    let mut reader = SeekableReader::<_, ArrayStore<128>>::with_array(Counter(0));
+    reader.seek(SeekFrom::Start(1000)).unwrap();
+    reader.seek(SeekFrom::Start(990)).unwrap();
+    reader.read_exact(&mut data).unwrap();
+    assert_eq!(data[0], (990 % 256) as u8);
+    assert_eq!(allocations(), before);
+}
+
+#[test]
+fn peek_beyond_the_buffer() {
+    let mut reader = SeekableReader::<_, ArrayStore<8>>::with_array(Counter(0));
+    let err = reader.peek(20).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert_eq!(reader.peek(8).unwrap(), &[0, 1, 2, 3, 4, 5, 6, 7]);
+    let mut data = [0; 20];
+    reader.read_exact(&mut data).unwrap();
+    assert_eq!(data[19], 19);
+}
+
+#[test]
+fn checkpoints_never_allocate() {
+    let mut reader = SeekableReader::<_, ArrayStore<64>>::with_array(Counter(0));
+    let before = allocations();
+    reader.read_exact(&mut [0; 10]).unwrap();
+    let mut checkpoint = reader.checkpoint();
+    checkpoint.read_exact(&mut [0; 20]).unwrap();
+    let mut nested = checkpoint.checkpoint();
+    nested.read_exact(&mut [0; 20]).unwrap();
+    drop(nested);
+    assert_eq!(checkpoint.get_stream_position(), 30);
+    assert_eq!(checkpoint.rollback().unwrap(), 10);
+    let parsed = reader.try_parse(|reader| {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        match byte[0] {
+            10 => Ok(byte[0]),
+            _ => Err(Error::from(ErrorKind::InvalidData)),
+        }
+    });
+    assert_eq!(parsed.unwrap(), 10);
+    assert_eq!(allocations(), before);
+}
+
+#[test]
+fn pinned_head_allocates_once() {
+    let mut reader = SeekableReader::<_, ArrayStore<16>>::with_array(Counter(0));
+    let before = allocations();
+    reader.pin_head(4).unwrap();
+    reader.read_exact(&mut [0; 100]).unwrap();
+    reader.seek(SeekFrom::Start(2)).unwrap();
+    let mut data = [0; 2];
+    reader.read_exact(&mut data).unwrap();
+    assert_eq!(data, [2, 3]);
+    assert_eq!(allocations(), before + 1);
+}
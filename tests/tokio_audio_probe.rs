@@ -0,0 +1,138 @@
+#![cfg(feature = "tokio")]
+
+use seekable_reader::{AsyncSeekableReader, OutOfWindowPolicy};
+/// The scenarios of `audio_probe.rs`, run against the tokio reader
+use std::io::{ErrorKind, Result, SeekFrom};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeekExt, ReadBuf};
+
+/// AsyncRead implementation
+struct ExampleRead {
+    counter: usize,
+}
+
+impl AsyncRead for ExampleRead {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<()>> {
+        for byte in buf.initialize_unfilled() {
+            *byte = self.counter as u8;
+            self.counter += 1;
+        }
+        buf.set_filled(buf.capacity());
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Counts up, but fails once at `fail_at`
+struct FailingRead {
+    counter: usize,
+    fail_at: Option<usize>,
+}
+
+impl AsyncRead for FailingRead {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<()>> {
+        if self.fail_at == Some(self.counter) {
+            self.fail_at = None;
+            return Poll::Ready(Err(ErrorKind::BrokenPipe.into()));
+        }
+        let len = buf
+            .remaining()
+            .min(self.fail_at.unwrap_or(usize::MAX) - self.counter);
+        for byte in &mut buf.initialize_unfilled()[..len] {
+            *byte = self.counter as u8;
+            self.counter += 1;
+        }
+        buf.advance(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn complex_read_seek() {
+    let reader = ExampleRead { counter: 0 };
+    let mut reader = AsyncSeekableReader::new(reader, 1_048_576);
+    let mut buf = vec![0; 2048];
+    reader.seek(SeekFrom::Current(0)).await.unwrap();
+    reader.read_exact(&mut buf[..4]).await.unwrap();
+    assert_eq!(buf[0..4], vec![0, 1, 2, 3]);
+    reader.seek(SeekFrom::Start(0)).await.unwrap();
+    reader.seek(SeekFrom::Current(0)).await.unwrap();
+    reader.read_exact(&mut buf[..2048]).await.unwrap();
+    for (i, byte) in buf.iter().enumerate() {
+        assert_eq!(*byte, (i % 256) as u8);
+    }
+    reader.seek(SeekFrom::Start(0)).await.unwrap();
+    reader.seek(SeekFrom::Current(0)).await.unwrap();
+    reader.read_exact(&mut buf[..27]).await.unwrap();
+    for (i, byte) in buf[..27].iter().enumerate() {
+        assert_eq!(*byte, (i % 256) as u8);
+    }
+    reader.read_exact(&mut buf[..1024]).await.unwrap();
+    for (i, byte) in buf[..1024].iter().enumerate() {
+        assert_eq!(*byte, ((i + 27) % 256) as u8);
+    }
+}
+
+#[tokio::test]
+async fn seek_forward_and_from_end() {
+    let source: Vec<u8> = (0..100).collect();
+    let mut reader = AsyncSeekableReader::new(source.as_slice(), 10);
+    let mut buf = [0; 5];
+    assert_eq!(reader.seek(SeekFrom::Start(50)).await.unwrap(), 50);
+    reader.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, [50, 51, 52, 53, 54]);
+    assert_eq!(reader.seek(SeekFrom::Current(-15)).await.unwrap(), 40);
+    assert!(reader.seek(SeekFrom::Start(0)).await.is_err());
+    assert_eq!(reader.seek(SeekFrom::End(-5)).await.unwrap(), 95);
+    let mut rest = vec![];
+    reader.read_to_end(&mut rest).await.unwrap();
+    assert_eq!(rest, [95, 96, 97, 98, 99]);
+    assert_eq!(reader.seek(SeekFrom::Start(200)).await.unwrap(), 100);
+}
+
+#[tokio::test]
+async fn seek_from_end_out_of_window() {
+    let source: Vec<u8> = (0..100).collect();
+    let mut reader = AsyncSeekableReader::new(source.as_slice(), 4);
+    reader.set_out_of_window_policy(OutOfWindowPolicy::Reopen);
+    reader.read_exact(&mut [0; 10]).await.unwrap();
+    assert!(reader.seek(SeekFrom::End(-50)).await.is_err());
+    assert_eq!(reader.seek(SeekFrom::End(-3)).await.unwrap(), 97);
+}
+
+#[tokio::test]
+async fn buf_read_lines() {
+    let source = b"first line\nsecond line\n";
+    let mut reader = AsyncSeekableReader::new(&source[..], 4);
+    let mut line = String::new();
+    reader.read_line(&mut line).await.unwrap();
+    assert_eq!(line, "first line\n");
+    reader.seek(SeekFrom::Current(-5)).await.unwrap();
+    let mut lines = reader.lines();
+    assert_eq!(lines.next_line().await.unwrap().unwrap(), "line");
+    assert_eq!(lines.next_line().await.unwrap().unwrap(), "second line");
+}
+
+#[tokio::test]
+async fn failed_seek_is_not_resumed() {
+    let inner = FailingRead {
+        counter: 0,
+        fail_at: Some(20),
+    };
+    let mut reader = AsyncSeekableReader::new(inner, 8);
+    reader.read_exact(&mut [0; 10]).await.unwrap();
+    let err = reader.seek(SeekFrom::Start(200)).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BrokenPipe);
+    assert_eq!(reader.seek(SeekFrom::Start(15)).await.unwrap(), 15);
+    let mut byte = [0];
+    reader.read_exact(&mut byte).await.unwrap();
+    assert_eq!(byte, [15]);
+}